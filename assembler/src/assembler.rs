@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
-use crate::instructions::{
-    Instruction, InstructionOrConstant, PreprocessorCommand, Register, Target,
+use crate::{
+    error::AssemblerError,
+    instructions::{Instruction, InstructionOrConstant, PreprocessorCommand, Register, Target},
 };
 
 enum PreprocessorConstant {
@@ -15,12 +16,19 @@ pub struct Assembler<'a> {
     constants: HashMap<String, PreprocessorConstant>,
     current_label: Option<String>,
     instructions: Vec<IntermediaryOutput>,
+    /// index of every instruction node and the address it starts at
+    instruction_addresses: Vec<(usize, u32)>,
 }
 
 #[derive(Debug, PartialEq)]
 enum IntermediaryOutput {
     Byte(u8),
-    ConstantReference { name: String, position: usize },
+    ConstantReference {
+        name: String,
+        position: usize,
+        /// labels resolve to their offset from `position`
+        relative: bool,
+    },
     ConstantPadding,
 }
 
@@ -31,6 +39,7 @@ impl<'a> Assembler<'a> {
             current_label: None,
             cursor: 0,
             instructions: Vec::new(),
+            instruction_addresses: Vec::new(),
             inner,
             constants: HashMap::new(),
         }
@@ -54,10 +63,11 @@ impl<'a> Assembler<'a> {
         }
     }
 
-    fn label_key(&self, sub_label: &'a str) -> Option<String> {
-        self.current_label
-            .as_ref()
-            .map(|parent| format!("{parent}@{sub_label}"))
+    fn sub_label_key(&self, sub_label: String) -> Result<String, AssemblerError> {
+        match &self.current_label {
+            Some(parent) => Ok(format!("{parent}@{sub_label}")),
+            None => Err(AssemblerError::SubLabelWithoutLabel(sub_label)),
+        }
     }
     fn instruction_byte(instruction: &Instruction) -> u8 {
         match instruction {
@@ -81,6 +91,13 @@ impl<'a> Assembler<'a> {
             Instruction::Jmp(_) => 0x11,
             Instruction::Jz(_, _) => 0x12,
             Instruction::Jnz(_, _) => 0x13,
+            Instruction::JmpRel(_) => 0x14,
+            Instruction::JzRel(_, _) => 0x15,
+            Instruction::JnzRel(_, _) => 0x16,
+            Instruction::Push(_) => 0x17,
+            Instruction::Pop(_) => 0x18,
+            Instruction::Call(_) => 0x19,
+            Instruction::Ret => 0x1a,
         }
     }
     fn push_immediate(instructions: &mut Vec<IntermediaryOutput>, immediate: u32) {
@@ -88,67 +105,81 @@ impl<'a> Assembler<'a> {
             instructions.push(IntermediaryOutput::Byte(byte));
         }
     }
-    fn push_constant_reference(
+    fn push_reference(
         instructions: &mut Vec<IntermediaryOutput>,
         name: String,
         position: usize,
+        relative: bool,
     ) {
-        instructions.push(IntermediaryOutput::ConstantReference { name, position });
+        instructions.push(IntermediaryOutput::ConstantReference {
+            name,
+            position,
+            relative,
+        });
         instructions.push(IntermediaryOutput::ConstantPadding);
         instructions.push(IntermediaryOutput::ConstantPadding);
         instructions.push(IntermediaryOutput::ConstantPadding);
     }
-    fn assemble_next(&mut self) -> bool {
+    /// appends the bytes of the operand `target`, registers are or'ed into the selector byte
+    /// `to_add[0]` shifted by `register_shift`, with `relative` a label or constant immediate
+    /// resolves to its offset from `position`
+    fn push_operand(
+        &self,
+        to_add: &mut Vec<IntermediaryOutput>,
+        target: Target,
+        position: usize,
+        register_shift: u8,
+        relative: bool,
+    ) -> Result<(), AssemblerError> {
+        let relative = relative && matches!(target, Target::Constant(_) | Target::SubConstant(_));
+        match target {
+            Target::Register(register) | Target::RegisterAddress(register) => {
+                match to_add.first_mut() {
+                    Some(IntermediaryOutput::Byte(v)) => {
+                        *v |= Self::register_byte(&register) << register_shift;
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            Target::Immediate(immediate) | Target::ImmediateAddress(immediate) => {
+                Self::push_immediate(to_add, immediate);
+            }
+            Target::Constant(label) | Target::ConstantAddress(label) => {
+                Self::push_reference(to_add, label, position, relative);
+            }
+            Target::SubConstant(label) | Target::SubConstantAddress(label) => {
+                let label = self.sub_label_key(label)?;
+                Self::push_reference(to_add, label, position, relative);
+            }
+        }
+        Ok(())
+    }
+    fn assemble_next(&mut self) -> Result<bool, AssemblerError> {
         use IntermediaryOutput::Byte;
         let current = self.current();
         match current {
             InstructionOrConstant::Instruction(instruction) => {
+                let address = self.instructions.len().try_into().unwrap();
+                self.instruction_addresses.push((self.cursor, address));
                 self.instructions
                     .push(Byte(Self::instruction_byte(&instruction)));
+                let relative = matches!(
+                    instruction,
+                    Instruction::JmpRel(_) | Instruction::JzRel(_, _) | Instruction::JnzRel(_, _)
+                );
                 match instruction {
-                    Instruction::Nop | Instruction::Hlt => self.step(),
-                    Instruction::Not(target) => {
+                    Instruction::Nop | Instruction::Hlt | Instruction::Ret => self.step(),
+                    Instruction::Not(target) | Instruction::Pop(target) => {
                         let selector = Self::selector_from_target(&target);
-
-                        match &target {
-                            Target::Register(register) | Target::RegisterAddress(register) => {
-                                let register = Self::register_byte(register);
-                                self.instructions.push(Byte(selector << 6 | register << 2));
-                            }
-                            Target::ImmediateAddress(immediate) => {
-                                self.instructions.push(Byte(selector << 6));
-                                Self::push_immediate(&mut self.instructions, *immediate);
-                            }
-                            Target::ConstantAddress(label) => {
-                                let instruction_position = self.instructions.len() - 1;
-                                self.instructions.push(Byte(selector << 6));
-                                Self::push_constant_reference(
-                                    &mut self.instructions,
-                                    label.to_owned(),
-                                    instruction_position,
-                                )
-                            }
-                            Target::SubConstantAddress(label) => {
-                                let instruction_position = self.instructions.len() - 1;
-                                self.instructions.push(Byte(selector << 6));
-
-                                let Some(label) = self.label_key(&label) else {
-                                    todo!("reached sub constant without label")
-                                };
-
-                                Self::push_constant_reference(
-                                    &mut self.instructions,
-                                    label,
-                                    instruction_position,
-                                );
-                            }
-                            Target::Immediate(_) | Target::Constant(_) | Target::SubConstant(_) => {
-                                unreachable!()
-                            }
-                        }
+                        let instruction_position = self.instructions.len() - 1;
+                        let mut to_add = vec![Byte(selector << 6)];
+                        self.push_operand(&mut to_add, target, instruction_position, 2, false)?;
+                        self.instructions.append(&mut to_add);
                         self.step();
                     }
                     Instruction::Jz(dest, src)
+                    | Instruction::JzRel(dest, src)
+                    | Instruction::JnzRel(dest, src)
                     | Instruction::Jnz(dest, src)
                     | Instruction::Mov(dest, src)
                     | Instruction::Or(dest, src)
@@ -167,102 +198,20 @@ impl<'a> Assembler<'a> {
                         let dest_selector = Self::selector_from_target(&dest);
                         let src_selector = Self::selector_from_target(&src);
                         let instruction_position = self.instructions.len() - 1;
-                        let mut to_add = Vec::new();
-                        to_add.push(Byte(dest_selector << 6 | src_selector << 4));
-
-                        match dest {
-                            Target::Register(register) | Target::RegisterAddress(register) => {
-                                match to_add.first_mut() {
-                                    Some(Byte(v)) => *v |= Self::register_byte(&register) << 2,
-                                    _ => unreachable!(),
-                                }
-                            }
-                            Target::Immediate(immediate) | Target::ImmediateAddress(immediate) => {
-                                Self::push_immediate(&mut to_add, immediate);
-                            }
-                            Target::Constant(label) | Target::ConstantAddress(label) => {
-                                Self::push_constant_reference(
-                                    &mut to_add,
-                                    label,
-                                    instruction_position,
-                                );
-                            }
-                            Target::SubConstant(label) | Target::SubConstantAddress(label) => {
-                                let Some(label) = self.label_key(&label) else {
-                                    todo!("reached sub label without label")
-                                };
-                                Self::push_constant_reference(
-                                    &mut to_add,
-                                    label,
-                                    instruction_position,
-                                );
-                            }
-                        }
-                        match src {
-                            Target::Register(register) | Target::RegisterAddress(register) => {
-                                match to_add.first_mut() {
-                                    Some(Byte(v)) => *v |= Self::register_byte(&register),
-                                    _ => unreachable!(),
-                                }
-                            }
-                            Target::Immediate(immediate) | Target::ImmediateAddress(immediate) => {
-                                Self::push_immediate(&mut to_add, immediate);
-                            }
-                            Target::Constant(label) | Target::ConstantAddress(label) => {
-                                Self::push_constant_reference(
-                                    &mut to_add,
-                                    label,
-                                    instruction_position,
-                                );
-                            }
-                            Target::SubConstant(label) | Target::SubConstantAddress(label) => {
-                                let Some(label) = self.label_key(&label) else {
-                                    todo!("reached sub label without label")
-                                };
-                                Self::push_constant_reference(
-                                    &mut to_add,
-                                    label,
-                                    instruction_position,
-                                );
-                            }
-                        }
+                        let mut to_add = vec![Byte(dest_selector << 6 | src_selector << 4)];
+                        self.push_operand(&mut to_add, dest, instruction_position, 2, relative)?;
+                        self.push_operand(&mut to_add, src, instruction_position, 0, false)?;
                         self.instructions.append(&mut to_add);
                         self.step();
                     }
-                    Instruction::Jmp(dest) => {
+                    Instruction::Jmp(dest)
+                    | Instruction::JmpRel(dest)
+                    | Instruction::Push(dest)
+                    | Instruction::Call(dest) => {
                         let dest_selector = Self::selector_from_target(&dest);
-                        let mut to_add = Vec::new();
-                        to_add.push(Byte(dest_selector << 6));
                         let instruction_position = self.instructions.len() - 1;
-
-                        match dest {
-                            Target::Register(register) | Target::RegisterAddress(register) => {
-                                match to_add.first_mut() {
-                                    Some(Byte(v)) => *v |= Self::register_byte(&register) << 2,
-                                    _ => unreachable!(),
-                                }
-                            }
-                            Target::Immediate(immediate) | Target::ImmediateAddress(immediate) => {
-                                Self::push_immediate(&mut to_add, immediate);
-                            }
-                            Target::Constant(label) | Target::ConstantAddress(label) => {
-                                Self::push_constant_reference(
-                                    &mut to_add,
-                                    label,
-                                    instruction_position,
-                                );
-                            }
-                            Target::SubConstant(label) | Target::SubConstantAddress(label) => {
-                                let Some(label) = self.label_key(&label) else {
-                                    todo!("reached sub label without label")
-                                };
-                                Self::push_constant_reference(
-                                    &mut to_add,
-                                    label,
-                                    instruction_position,
-                                );
-                            }
-                        }
+                        let mut to_add = vec![Byte(dest_selector << 6)];
+                        self.push_operand(&mut to_add, dest, instruction_position, 2, relative)?;
                         self.instructions.append(&mut to_add);
                         self.step();
                     }
@@ -293,9 +242,7 @@ impl<'a> Assembler<'a> {
                     self.step();
                 }
                 PreprocessorCommand::DefineSub(label, value) => {
-                    let Some(label) = self.label_key(&label) else {
-                        todo!("sublabel without label");
-                    };
+                    let label = self.sub_label_key(label)?;
 
                     let existing_constant = self
                         .constants
@@ -347,9 +294,7 @@ impl<'a> Assembler<'a> {
             InstructionOrConstant::SubLabel(label) => {
                 let position = self.instructions.len();
                 let position = position.try_into().unwrap();
-                let Some(label_key) = self.label_key(&label) else {
-                    todo!("sublabel without label");
-                };
+                let label_key = self.sub_label_key(label.clone())?;
                 let existing_label = self
                     .constants
                     .insert(label_key, PreprocessorConstant::Label(position));
@@ -365,15 +310,22 @@ impl<'a> Assembler<'a> {
                 }
                 self.step();
             }
-            InstructionOrConstant::EOF => return true,
+            InstructionOrConstant::EOF => return Ok(true),
         }
-        false
+        Ok(false)
     }
+    /// # Panics
+    /// if the nodes can't be assembled, see [`Assembler::try_assemble`]
     #[must_use]
-    pub fn assemble(mut self) -> Vec<u8> {
+    pub fn assemble(&mut self) -> Vec<u8> {
+        self.try_assemble().unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// # Errors
+    /// if a sub label is declared or used before the first label
+    pub fn try_assemble(&mut self) -> Result<Vec<u8>, AssemblerError> {
         log::info!("assembling...");
         loop {
-            if self.assemble_next() {
+            if self.assemble_next()? {
                 break;
             }
         }
@@ -389,11 +341,18 @@ impl<'a> Assembler<'a> {
             };
             match next {
                 Byte(v) => out.push(*v),
-                ConstantReference { name, position } => {
+                ConstantReference {
+                    name,
+                    position,
+                    relative,
+                } => {
                     let Some(value) = self.constants.get(name.as_str()) else {
                         todo!("error: unrecognized constant '{name}' with value {position}");
                     };
                     let value = match value {
+                        PreprocessorConstant::Label(value) if *relative => {
+                            value.wrapping_sub(*position as u32)
+                        }
                         PreprocessorConstant::Define(value)
                         | PreprocessorConstant::Label(value) => *value,
                     };
@@ -424,7 +383,28 @@ impl<'a> Assembler<'a> {
         );
         log::info!("done");
 
-        out
+        Ok(out)
+    }
+    /// labels and their addresses ordered by address, call after [`Assembler::assemble`],
+    /// sub labels are named `<label>@<sub label>`
+    #[must_use]
+    pub fn symbols(&self) -> Vec<(u32, String)> {
+        let mut symbols: Vec<_> = self
+            .constants
+            .iter()
+            .filter_map(|(name, constant)| match constant {
+                PreprocessorConstant::Label(address) => Some((*address, name.clone())),
+                PreprocessorConstant::Define(_) => None,
+            })
+            .collect();
+        symbols.sort();
+        symbols
+    }
+    /// the index of every instruction in the assembled nodes and its address in order,
+    /// call after [`Assembler::assemble`]
+    #[must_use]
+    pub fn instruction_addresses(&self) -> &[(usize, u32)] {
+        &self.instruction_addresses
     }
     pub fn step(&mut self) {
         self.cursor += 1;
@@ -434,3 +414,62 @@ impl<'a> Assembler<'a> {
         self.inner[self.cursor].clone()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{error::AssemblerError, instructions::InstructionOrConstant, Assembler, Parser};
+
+    fn assemble(source: &str) -> Result<Vec<u8>, AssemblerError> {
+        let nodes = Parser::new(source.as_bytes())
+            .parse()
+            .into_iter()
+            .collect::<Result<Vec<InstructionOrConstant>, _>>()
+            .unwrap();
+        Assembler::new(&nodes).try_assemble()
+    }
+
+    #[test]
+    fn relative_targets_are_offsets_from_the_jump() {
+        // the jump is one byte past the label
+        assert_eq!(
+            assemble("main:\n.loop:\nnop\njmp rel .loop\n").unwrap(),
+            [0x00, 0x14, 0b0100_0000, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+        // the condition is never relative
+        assert_eq!(
+            assemble("main:\nnop\njz rel main, main\n").unwrap(),
+            [
+                0x00,
+                0x15,
+                0b0101_0000,
+                0xFF,
+                0xFF,
+                0xFF,
+                0xFF,
+                0x00,
+                0x00,
+                0x00,
+                0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn sub_labels_need_a_label() {
+        let sources = [
+            "jmp rel .loop",
+            "jz rel .loop, r0",
+            "mov r0, .loop",
+            "not [.loop]",
+            ".loop:",
+            "%define .loop 1",
+        ];
+        for source in sources {
+            assert_eq!(
+                assemble(source),
+                Err(AssemblerError::SubLabelWithoutLabel(String::from("loop"))),
+                "{source}"
+            );
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Formatter},
+};
 
 #[derive(Clone, Debug)]
 pub struct Position {
@@ -15,3 +18,22 @@ pub struct Error<'a> {
 }
 
 pub type Result<'a, T> = std::result::Result<T, Error<'a>>;
+
+/// errors found while assembling parsed nodes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssemblerError {
+    /// a sub label was declared or used before the first label
+    SubLabelWithoutLabel(String),
+}
+
+impl Display for AssemblerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SubLabelWithoutLabel(label) => {
+                write!(f, "sub label '.{label}' is used before any label")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssemblerError {}
@@ -60,6 +60,15 @@ pub enum Instruction {
     Jmp(Target),
     Jz(Target, Target),
     Jnz(Target, Target),
+    /// targets are offsets from the start of the instruction, labels are converted to them
+    JmpRel(Target),
+    JzRel(Target, Target),
+    JnzRel(Target, Target),
+    /// only run by vms with the stack profile
+    Push(Target),
+    Pop(Target),
+    Call(Target),
+    Ret,
 }
 
 #[derive(Debug)]
@@ -84,6 +93,10 @@ pub enum NamedInstruction {
     Jmp,
     Jz,
     Jnz,
+    Push,
+    Pop,
+    Call,
+    Ret,
 }
 
 #[must_use]
@@ -109,6 +122,10 @@ pub fn instruction_from_text(text: &[u8]) -> Option<NamedInstruction> {
         b"jmp" => Some(NamedInstruction::Jmp),
         b"jz" => Some(NamedInstruction::Jz),
         b"jnz" => Some(NamedInstruction::Jnz),
+        b"push" => Some(NamedInstruction::Push),
+        b"pop" => Some(NamedInstruction::Pop),
+        b"call" => Some(NamedInstruction::Call),
+        b"ret" => Some(NamedInstruction::Ret),
         _ => None,
     }
 }
@@ -37,6 +37,16 @@ struct MyOptions {
     #[options(help = "write output to <file>")]
     out: OutFileWrapper,
 
+    #[options(
+        help = "write the address of every label to <file>, one `<address> <label>` per line"
+    )]
+    symbols: Option<String>,
+
+    #[options(
+        help = "write the source line of every instruction to <file>, one `<address> <line>` per line"
+    )]
+    lines: Option<String>,
+
     #[options(help = "log level (off, debug, info, warn, error)", default = "info")]
     log_level: LevelFilter,
 }
@@ -49,6 +59,8 @@ fn main() {
     let MyOptions {
         file: file_contents,
         out: out_file,
+        symbols: symbols_file,
+        lines: lines_file,
         log_level,
         ..
     } = Options::parse_args_default_or_exit();
@@ -60,7 +72,7 @@ fn main() {
         .unwrap();
 
     let parser = vc2_assembler::Parser::new(file_contents.as_bytes());
-    let node = parser.parse();
+    let (node, lines) = parser.parse_with_lines();
     let (ok, err): (Vec<InstructionOrConstant>, Vec<vc2_assembler::error::Error>) =
         node.into_iter().partition_map(|v| match v {
             Ok(v) => Either::Left(v),
@@ -93,8 +105,14 @@ fn main() {
         }
         std::process::exit(1);
     }
-    let assembler = Assembler::new(&ok);
-    let out = assembler.assemble();
+    let mut assembler = Assembler::new(&ok);
+    let out = match assembler.try_assemble() {
+        Ok(out) => out,
+        Err(err) => {
+            log::error!("{err}");
+            std::process::exit(1);
+        }
+    };
 
     log::debug!("nodes:");
     log::debug!("{ok:#?}");
@@ -107,4 +125,21 @@ fn main() {
     );
 
     fs::write(out_file.0, out).unwrap();
+    if let Some(symbols_file) = symbols_file {
+        let symbols: String = assembler
+            .symbols()
+            .into_iter()
+            .map(|(address, label)| format!("{address:#010X} {label}\n"))
+            .collect();
+        fs::write(symbols_file, symbols).unwrap();
+    }
+    if let Some(lines_file) = lines_file {
+        // errors exit above, so node indices match `lines`
+        let lines: String = assembler
+            .instruction_addresses()
+            .iter()
+            .map(|(node, address)| format!("{address:#010X} {}\n", lines[*node]))
+            .collect();
+        fs::write(lines_file, lines).unwrap();
+    }
 }
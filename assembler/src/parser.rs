@@ -11,6 +11,8 @@ pub struct Parser<'a> {
     cursor: usize,
     character: usize,
     line: usize,
+    /// line the last parsed node starts on
+    node_line: usize,
     inner: &'a [u8],
 }
 
@@ -216,6 +218,22 @@ impl<'a> Parser<'a> {
             word_end,
         )
     }
+    /// skips `keyword` if it's the next word and a target follows it on the same line,
+    /// so a label with the same name can still be used as a target
+    fn consume_keyword(&mut self, keyword: &[u8]) -> bool {
+        let (cursor, line, character) = (self.cursor, self.line, self.character);
+        self.skip_whitespace();
+        if !self.done() && self.take_id().0 == keyword {
+            while !self.done() && matches!(self.current(), b' ' | b'\t') {
+                self.step();
+            }
+            if !self.done() && !matches!(self.current(), b'\n' | b'\r' | b',' | b';') {
+                return true;
+            }
+        }
+        (self.cursor, self.line, self.character) = (cursor, line, character);
+        false
+    }
     fn invalid_character_error(&mut self, message: Cow<'a, str>) -> Error<'a> {
         let from = self.position();
         let to = self.position();
@@ -250,9 +268,22 @@ impl<'a> Parser<'a> {
             NamedInstruction::IDiv => InstructionConstructor::Two(Instruction::IDiv),
             NamedInstruction::Rem => InstructionConstructor::Two(Instruction::Rem),
             NamedInstruction::Cmp => InstructionConstructor::Two(Instruction::Cmp),
+            NamedInstruction::Jmp if self.consume_keyword(b"rel") => {
+                InstructionConstructor::One(Instruction::JmpRel)
+            }
+            NamedInstruction::Jz if self.consume_keyword(b"rel") => {
+                InstructionConstructor::Two(Instruction::JzRel)
+            }
+            NamedInstruction::Jnz if self.consume_keyword(b"rel") => {
+                InstructionConstructor::Two(Instruction::JnzRel)
+            }
             NamedInstruction::Jmp => InstructionConstructor::One(|target| Instruction::Jmp(target)),
             NamedInstruction::Jz => InstructionConstructor::Two(Instruction::Jz),
             NamedInstruction::Jnz => InstructionConstructor::Two(Instruction::Jnz),
+            NamedInstruction::Push => InstructionConstructor::One(Instruction::Push),
+            NamedInstruction::Pop => InstructionConstructor::One(Instruction::Pop),
+            NamedInstruction::Call => InstructionConstructor::One(Instruction::Call),
+            NamedInstruction::Ret => InstructionConstructor::None(Instruction::Ret),
         };
         let instruction = match constructor {
             InstructionConstructor::None(instruction) => instruction,
@@ -442,6 +473,7 @@ impl<'a> Parser<'a> {
         if self.done() {
             return Ok(InstructionOrConstant::EOF);
         };
+        self.node_line = self.line;
         log::debug!("current: {}", self.current() as char);
         match self.current() {
             b'%' => self.parse_preprocessor_command(),
@@ -467,18 +499,26 @@ impl<'a> Parser<'a> {
         }
     }
     #[must_use]
-    pub fn parse(mut self) -> Vec<Result<'a, InstructionOrConstant>> {
+    pub fn parse(self) -> Vec<Result<'a, InstructionOrConstant>> {
+        self.parse_with_lines().0
+    }
+    /// like [`Parser::parse`], also returns the line every node starts on
+    #[must_use]
+    pub fn parse_with_lines(mut self) -> (Vec<Result<'a, InstructionOrConstant>>, Vec<usize>) {
         log::info!("parsing...");
         let mut instructions = Vec::new();
+        let mut lines = Vec::new();
         loop {
             if self.done() {
                 instructions.push(Ok(InstructionOrConstant::EOF));
+                lines.push(self.line);
                 break;
             }
-            instructions.push(self.parse_single())
+            instructions.push(self.parse_single());
+            lines.push(self.node_line);
         }
         log::info!("done");
-        instructions
+        (instructions, lines)
     }
     #[must_use]
     pub fn new(inner: &'a [u8]) -> Self {
@@ -486,6 +526,7 @@ impl<'a> Parser<'a> {
             inner,
             character: 1,
             line: 1,
+            node_line: 1,
             cursor: 0,
         }
     }
@@ -555,7 +596,7 @@ mod test {
     use pretty_assertions::assert_eq;
 
     use crate::{
-        instructions::{Register, Target},
+        instructions::{Instruction, InstructionOrConstant, Register, Target},
         Parser,
     };
 
@@ -611,4 +652,48 @@ mod test {
         assert_eq!(Target::ImmediateAddress(4321), imm);
         assert!(parser.done());
     }
+
+    #[test]
+    fn parse_relative_jumps() {
+        let nodes = Parser::new(b"jmp rel .loop\njz rel 8, r0\njmp rel\njnz rel, r1").parse();
+        let instructions: Vec<_> = nodes
+            .into_iter()
+            .map(|node| match node.unwrap() {
+                InstructionOrConstant::Instruction(instruction) => Some(instruction),
+                _ => None,
+            })
+            .collect();
+        assert!(matches!(
+            &instructions[0],
+            Some(Instruction::JmpRel(Target::SubConstant(label))) if label == "loop"
+        ));
+        assert!(matches!(
+            &instructions[1],
+            Some(Instruction::JzRel(
+                Target::Immediate(8),
+                Target::Register(Register::GeneralPurpose0)
+            ))
+        ));
+        // a label named `rel`
+        assert!(matches!(
+            &instructions[2],
+            Some(Instruction::Jmp(Target::Constant(label))) if label == "rel"
+        ));
+        assert!(matches!(
+            &instructions[3],
+            Some(Instruction::Jnz(Target::Constant(label), _)) if label == "rel"
+        ));
+    }
+
+    #[test]
+    fn nodes_know_their_line() {
+        let (nodes, lines) =
+            Parser::new(b"; header\nmain:\n\n  mov r0, 1 ; one\n  hlt\n").parse_with_lines();
+        assert!(matches!(&nodes[0], Ok(InstructionOrConstant::Label(label)) if label == "main"));
+        assert!(matches!(
+            &nodes[2],
+            Ok(InstructionOrConstant::Instruction(Instruction::Hlt))
+        ));
+        assert_eq!(lines[..3], [2, 4, 5]);
+    }
 }
@@ -0,0 +1,113 @@
+use vc2_vm::{Device, SnapshotError, Word};
+
+pub const KEYBOARD_ENABLED_LOCATION: u32 = 0x2020;
+pub const KEY_EVENT_TYPE_LOCATION: u32 = 0x2024;
+pub const KEYCODE_LOCATION: u32 = 0x2028;
+pub const KEY_EVENT_CALLBACK_LOCATION: u32 = 0x202C;
+
+pub const SCREEN_ENABLED_LOCATION: u32 = 0x2030;
+pub const SCREEN_VRAM_ADDRESS_LOCATION: u32 = 0x2034;
+pub const SCREEN_WIDTH_LOCATION: u32 = 0x2038;
+pub const SCREEN_HEIGHT_LOCATION: u32 = 0x203C;
+
+pub const INTERRUPT_CONTROLLER_LOCATION: u32 = 0x2040;
+/// raised on every key event, after the event type and keycode are written
+pub const KEYBOARD_INTERRUPT: u8 = 0;
+
+pub const SCREEN_WIDTH: u32 = 120;
+pub const SCREEN_HEIGHT: u32 = 96;
+pub const SCREEN_VRAM_ADDRESS: u32 = 0x3000;
+
+/// the last key event, written by the window and read by the program
+#[derive(Default)]
+pub struct Keyboard {
+    event_type: Word,
+    keycode: Word,
+    callback: Word,
+}
+
+impl Device for Keyboard {
+    fn peek(&self, offset: Word) -> Word {
+        match offset + KEYBOARD_ENABLED_LOCATION {
+            KEYBOARD_ENABLED_LOCATION => 1,
+            KEY_EVENT_TYPE_LOCATION => self.event_type,
+            KEYCODE_LOCATION => self.keycode,
+            KEY_EVENT_CALLBACK_LOCATION => self.callback,
+            _ => 0,
+        }
+    }
+    fn write(&mut self, offset: Word, value: Word) {
+        match offset + KEYBOARD_ENABLED_LOCATION {
+            KEY_EVENT_TYPE_LOCATION => self.event_type = value,
+            KEYCODE_LOCATION => self.keycode = value,
+            KEY_EVENT_CALLBACK_LOCATION => self.callback = value,
+            _ => {}
+        }
+    }
+    fn save_state(&self) -> Vec<u8> {
+        [self.event_type, self.keycode, self.callback]
+            .iter()
+            .flat_map(|word| word.to_be_bytes())
+            .collect()
+    }
+    fn restore_state(&mut self, state: &[u8]) -> Result<(), SnapshotError> {
+        let [event_type, keycode, callback] = words(state)?;
+        *self = Self {
+            event_type,
+            keycode,
+            callback,
+        };
+        Ok(())
+    }
+}
+
+/// the screen's configuration, the pixels are read from ram at the vram address
+pub struct Screen {
+    vram_address: Word,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self {
+            vram_address: SCREEN_VRAM_ADDRESS,
+        }
+    }
+}
+
+impl Device for Screen {
+    fn peek(&self, offset: Word) -> Word {
+        match offset + SCREEN_ENABLED_LOCATION {
+            SCREEN_ENABLED_LOCATION => 1,
+            SCREEN_VRAM_ADDRESS_LOCATION => self.vram_address,
+            SCREEN_WIDTH_LOCATION => SCREEN_WIDTH,
+            SCREEN_HEIGHT_LOCATION => SCREEN_HEIGHT,
+            _ => 0,
+        }
+    }
+    /// only the vram address can be changed, e.g. to render from a second buffer
+    fn write(&mut self, offset: Word, value: Word) {
+        if offset + SCREEN_ENABLED_LOCATION == SCREEN_VRAM_ADDRESS_LOCATION {
+            self.vram_address = value;
+        }
+    }
+    fn save_state(&self) -> Vec<u8> {
+        self.vram_address.to_be_bytes().to_vec()
+    }
+    fn restore_state(&mut self, state: &[u8]) -> Result<(), SnapshotError> {
+        let [vram_address] = words(state)?;
+        self.vram_address = vram_address;
+        Ok(())
+    }
+}
+
+/// splits a saved device state into `N` big endian words
+fn words<const N: usize>(state: &[u8]) -> Result<[Word; N], SnapshotError> {
+    let len = N * 4;
+    if state.len() != len {
+        return Err(SnapshotError::UnexpectedEnd { len });
+    }
+    Ok(std::array::from_fn(|index| {
+        let word = state[index * 4..index * 4 + 4].try_into().expect("4 bytes");
+        Word::from_be_bytes(word)
+    }))
+}
@@ -2,22 +2,43 @@ use gumdrop::Options;
 use log::LevelFilter;
 use simple_logger::SimpleLogger;
 use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
     io::{self, Write},
     sync::{Arc, Mutex},
     time::Instant,
 };
 use utils::parse_integer;
 
-use vc2_vm::Vm;
+use vc2_vm::{
+    BranchCoverage, Coverage, Endianness, ExecutionEngine, InputRecording, MemoryBackend,
+    Permissions, Profile, Profiler, Register, ShiftMode, Snapshot, StepOutcome, StopReason,
+    VmConfig, WatchKind, WatchpointHit, Word,
+};
 
+mod symbols;
 mod utils;
 
+use symbols::Symbols;
+
+/// the profiler and coverage are only attached while they're recording
+type Vm = vc2_vm::Vm<(Option<Profiler>, Option<Coverage>)>;
+
+/// amount of instructions `eval` runs before releasing the vm to the peripherals
+const EVAL_BATCH_SIZE: u64 = 10_000;
+
+#[cfg(feature = "peripherals")]
+mod devices;
 #[cfg(feature = "peripherals")]
 mod peripherals;
 
-fn vm_from_file(file_name: &str, memory_bytes: usize) -> io::Result<Vm> {
+fn create_vm(instructions: Vec<u8>, config: VmConfig) -> Vm {
+    vc2_vm::Vm::new(instructions, config).with_observer((None, None))
+}
+
+fn vm_from_file(file_name: &str, config: VmConfig) -> io::Result<Vm> {
     let instructions = std::fs::read(file_name)?;
-    Ok(Vm::new(instructions, memory_bytes))
+    Ok(create_vm(instructions, config))
 }
 
 enum WordFormat {
@@ -56,37 +77,129 @@ fn word_format(cmd: &str, word: Option<&str>) -> Option<WordFormat> {
     }
 }
 
-fn initialize_vm(vm: &mut Vm) -> Result<(), String> {
+fn initialize_vm(vm: &mut Vm, engine: ExecutionEngine) {
+    vm.set_execution_engine(engine);
     #[cfg(feature = "peripherals")]
     {
-        vm.set_memory_value(&peripherals::SCREEN_ENABLED_LOCATION, 1)?;
-        vm.set_memory_value(
-            &peripherals::SCREEN_VRAM_ADDRESS_LOCATION,
-            peripherals::SCREEN_VRAM_ADDRESS,
-        )?;
-        vm.set_memory_value(
-            &peripherals::SCREEN_WIDTH_LOCATION,
-            peripherals::SCREEN_WIDTH,
-        )?;
-        vm.set_memory_value(
-            &peripherals::SCREEN_HEIGHT_LOCATION,
-            peripherals::SCREEN_HEIGHT,
-        )?;
-        vm.set_memory_value(
-            &peripherals::SCREEN_HEIGHT_LOCATION,
-            peripherals::SCREEN_HEIGHT,
-        )?;
-
-        vm.set_memory_value(&peripherals::KEYBOARD_ENABLED_LOCATION, 1)?;
+        use devices::*;
+        vm.map_device(
+            KEYBOARD_ENABLED_LOCATION..KEY_EVENT_CALLBACK_LOCATION + 4,
+            Box::<Keyboard>::default(),
+        );
+        vm.map_device(
+            SCREEN_ENABLED_LOCATION..SCREEN_HEIGHT_LOCATION + 4,
+            Box::<Screen>::default(),
+        );
+        vm.map_interrupt_controller(INTERRUPT_CONTROLLER_LOCATION);
+    }
+}
+
+fn print_watchpoint_hit(hit: &WatchpointHit) {
+    let WatchpointHit {
+        pc,
+        address,
+        kind,
+        old,
+        new,
+    } = hit;
+    println!("{kind:?} watchpoint hit at {address:#010X} by instruction at {pc:#010X}: {old:#010X} -> {new:#010X}");
+}
+
+/// the `count` hottest instructions and memory addresses, and labels if `symbols` are given
+fn print_profile(profiler: &Profiler, count: usize, symbols: Option<&Symbols>) {
+    let total = profiler.total_cycles();
+    let share = |cycles: u64| cycles as f64 * 100.0 / total.max(1) as f64;
+    let describe = |address: Word| {
+        symbols
+            .and_then(|symbols| symbols.describe(address))
+            .map_or(String::new(), |label| format!(" {label}"))
+    };
+    let data_label = |address: Word| {
+        symbols
+            .and_then(|symbols| symbols.label_at(address))
+            .map_or(String::new(), |label| format!(" {label}"))
+    };
+    println!("[#] instructions ({total} cycles):");
+    for (address, profile) in profiler.hottest_instructions().into_iter().take(count) {
+        println!(
+            "- {address:#010X}{}: {} executions, {} cycles ({:.1}%)",
+            describe(address),
+            profile.executions,
+            profile.cycles,
+            share(profile.cycles)
+        );
+    }
+    if let Some(symbols) = symbols {
+        let mut labels: BTreeMap<&str, u64> = BTreeMap::new();
+        for (address, profile) in profiler.instructions() {
+            let label = symbols.label(address).unwrap_or("(no label)");
+            *labels.entry(label).or_default() += profile.cycles;
+        }
+        let mut labels: Vec<_> = labels.into_iter().collect();
+        labels.sort_by_key(|(label, cycles)| (Reverse(*cycles), *label));
+        println!("[#] labels:");
+        for (label, cycles) in labels.into_iter().take(count) {
+            println!("- {label}: {cycles} cycles ({:.1}%)", share(cycles));
+        }
+    }
+    println!("[#] memory:");
+    for (address, profile) in profiler.hottest_memory().into_iter().take(count) {
+        println!(
+            "- {address:#010X}{}: {} reads, {} writes",
+            data_label(address),
+            profile.reads,
+            profile.writes
+        );
     }
+}
 
-    Ok(())
+/// every conditional jump and which way it went, with labels if `symbols` are given
+fn print_coverage(coverage: &Coverage, symbols: Option<&Symbols>) {
+    let describe = |address: Word| {
+        symbols
+            .and_then(|symbols| symbols.describe(address))
+            .map_or(String::new(), |label| format!(" {label}"))
+    };
+    println!(
+        "[#] coverage: {} instructions executed",
+        coverage.instructions().count()
+    );
+    println!("[#] branches:");
+    for (address, branch) in coverage.branches() {
+        let BranchCoverage { taken, not_taken } = branch;
+        let never = match (taken, not_taken) {
+            (0, _) => " (never taken)",
+            (_, 0) => " (never falls through)",
+            _ => "",
+        };
+        println!(
+            "- {address:#010X}{}: taken {taken}, not taken {not_taken}{never}",
+            describe(address)
+        );
+    }
+}
+
+fn waits_for_interrupt(vm: &Vm) -> bool {
+    #[cfg(feature = "peripherals")]
+    {
+        use devices::*;
+        use vc2_vm::INTERRUPT_MASK_OFFSET;
+        let callback = vm.memory_value(&KEY_EVENT_CALLBACK_LOCATION);
+        let mask = vm.memory_value(&(INTERRUPT_CONTROLLER_LOCATION + INTERRUPT_MASK_OFFSET));
+        callback.is_ok_and(|callback| callback != 0) || mask.is_ok_and(|mask| mask != 0)
+    }
+    #[cfg(not(feature = "peripherals"))]
+    {
+        let _ = vm;
+        false
+    }
 }
 
 fn execute_cmd(
     vm: &mut Arc<Mutex<Option<Vm>>>,
     buffer: &mut dyn Iterator<Item = &str>,
-    memory: usize,
+    config: VmConfig,
+    engine: ExecutionEngine,
 ) -> CmdResult {
     let help_menu = include_str!("help.txt");
 
@@ -98,10 +211,10 @@ fn execute_cmd(
                 println!("missing file name after `{cmd}` command");
                 return CmdResult::Continue;
             };
-            match vm_from_file(file_name, memory) {
+            match vm_from_file(file_name, config) {
                 Ok(mut new_vm) => {
                     let mut vm = vm.lock().unwrap();
-                    initialize_vm(&mut new_vm).unwrap();
+                    initialize_vm(&mut new_vm, engine);
                     *vm = Some(new_vm);
                     drop(vm);
                     println!("vm loaded from file '{file_name}'")
@@ -109,6 +222,114 @@ fn execute_cmd(
                 Err(err) => println!("error loading vm from file '{file_name}': {err}"),
             }
         }
+        Some(cmd @ "save") => {
+            let Some(file_name) = buffer.next() else {
+                println!("missing file name after `{cmd}` command");
+                return CmdResult::Continue;
+            };
+            let vm = vm.lock().unwrap();
+            let Some(ref vm) = *vm else {
+                println!("vm not started, try `help`");
+                return CmdResult::Continue;
+            };
+            match std::fs::write(file_name, vm.snapshot().to_bytes()) {
+                Ok(()) => println!("snapshot saved to '{file_name}'"),
+                Err(err) => println!("error saving snapshot to '{file_name}': {err}"),
+            }
+        }
+        Some(cmd @ "record") => {
+            let mut vm = vm.lock().unwrap();
+            let Some(ref mut vm) = *vm else {
+                println!("vm not started, try `help`");
+                return CmdResult::Continue;
+            };
+            match buffer.next() {
+                Some("start") => {
+                    vm.start_recording();
+                    println!("recording inputs");
+                }
+                Some("stop") => {
+                    let Some(file_name) = buffer.next() else {
+                        println!("missing file name after `{cmd} stop`");
+                        return CmdResult::Continue;
+                    };
+                    let Some(recording) = vm.stop_recording() else {
+                        println!("not recording, try `{cmd} start`");
+                        return CmdResult::Continue;
+                    };
+                    match std::fs::write(file_name, recording.to_bytes()) {
+                        Ok(()) => println!(
+                            "recording of {} inputs saved to '{file_name}'",
+                            recording.events.len()
+                        ),
+                        Err(err) => println!("error saving recording to '{file_name}': {err}"),
+                    }
+                }
+                _ => println!("expected `start` or `stop` after `{cmd}`"),
+            }
+        }
+        Some(cmd @ "replay") => {
+            let Some(file_name) = buffer.next() else {
+                println!("missing file name after `{cmd}` command");
+                return CmdResult::Continue;
+            };
+            let recording = match std::fs::read(file_name) {
+                Ok(bytes) => InputRecording::from_bytes(&bytes),
+                Err(err) => {
+                    println!("error reading recording from '{file_name}': {err}");
+                    return CmdResult::Continue;
+                }
+            };
+            match recording {
+                Ok(recording) => {
+                    let mut vm = vm.lock().unwrap();
+                    let vm = vm.get_or_insert_with(|| {
+                        let mut vm = create_vm(Vec::new(), config.memory_size(0));
+                        initialize_vm(&mut vm, engine);
+                        vm
+                    });
+                    if let Err(err) = vm.replay(&recording) {
+                        println!("error replaying '{file_name}': {err}");
+                        return CmdResult::Continue;
+                    }
+                    println!(
+                        "replaying {} inputs from '{file_name}'",
+                        recording.events.len()
+                    );
+                }
+                Err(err) => println!("error replaying '{file_name}': {err}"),
+            }
+        }
+        Some(cmd @ "restore") => {
+            let Some(file_name) = buffer.next() else {
+                println!("missing file name after `{cmd}` command");
+                return CmdResult::Continue;
+            };
+            let snapshot = match std::fs::read(file_name) {
+                Ok(bytes) => Snapshot::from_bytes(&bytes),
+                Err(err) => {
+                    println!("error reading snapshot from '{file_name}': {err}");
+                    return CmdResult::Continue;
+                }
+            };
+            match snapshot {
+                Ok(snapshot) => {
+                    let mut vm = vm.lock().unwrap();
+                    let vm = vm.get_or_insert_with(|| {
+                        let mut vm = create_vm(Vec::new(), config.memory_size(0));
+                        initialize_vm(&mut vm, engine);
+                        vm
+                    });
+                    match vm.restore(&snapshot) {
+                        Ok(()) => println!("vm restored from snapshot '{file_name}'"),
+                        Err(err) => {
+                            println!("error restoring snapshot from '{file_name}': {err}")
+                        }
+                    }
+                }
+                Err(err) => println!("error restoring snapshot from '{file_name}': {err}"),
+            }
+        }
         Some("step") => {
             let mut vm = vm.lock().unwrap();
             let Some(ref mut vm) = *vm else {
@@ -122,11 +343,54 @@ fn execute_cmd(
                 return CmdResult::Continue;
             };
 
-            (0..amount).for_each(|_| {
-                if let Err(err) = vm.run_next_instruction() {
-                    println!("vm unable to step: {err}")
+            for _ in 0..amount {
+                match vm.run_next_instruction() {
+                    Ok(StepOutcome::Halted | StepOutcome::WaitingForInterrupt) => {
+                        println!("vm halted");
+                        break;
+                    }
+                    Ok(StepOutcome::Breakpoint(address)) => {
+                        println!("breakpoint hit at {address:#010X}");
+                        break;
+                    }
+                    Ok(StepOutcome::Watchpoint(hit)) => {
+                        print_watchpoint_hit(&hit);
+                        break;
+                    }
+                    Ok(_) => {}
+                    Err(err) => println!("vm unable to step: {err}"),
                 }
-            });
+            }
+        }
+        Some(cmd @ ("history" | "back" | "rewind")) => {
+            let mut vm = vm.lock().unwrap();
+            let Some(ref mut vm) = *vm else {
+                println!("vm not started, try `help`");
+                return CmdResult::Continue;
+            };
+            let amount = buffer.next().and_then(|v| parse_integer(v).ok());
+            let Some(amount) = amount else {
+                println!("invalid amount after `{cmd}`");
+                return CmdResult::Continue;
+            };
+            match cmd {
+                "history" => vm.set_history_capacity(amount as usize),
+                "back" => {
+                    let undone = vm.step_back(amount);
+                    println!(
+                        "stepped back {undone} instructions to instruction {}",
+                        vm.instruction_count()
+                    );
+                }
+                _ => {
+                    if !vm.rewind_to(amount) {
+                        println!(
+                            "instruction {amount} is not in the history, the oldest is {}",
+                            vm.instruction_count() - vm.history_len() as u64
+                        );
+                    }
+                }
+            }
         }
         Some("inline") => {
             let mut bytes = Vec::new();
@@ -150,8 +414,8 @@ fn execute_cmd(
                 });
             }
             let mut vm = vm.lock().unwrap();
-            let mut new_vm = Vm::new(bytes, memory);
-            initialize_vm(&mut new_vm).unwrap();
+            let mut new_vm = create_vm(bytes, config);
+            initialize_vm(&mut new_vm, engine);
             *vm = Some(new_vm);
             println!("vm loaded from bytes");
             drop(vm);
@@ -169,7 +433,7 @@ fn execute_cmd(
             let buffer = buffer.collect::<Vec<_>>();
             for _ in 0..amount {
                 let mut buffer = buffer.clone().into_iter();
-                let result = execute_cmd(vm, &mut buffer, memory);
+                let result = execute_cmd(vm, &mut buffer, config, engine);
                 if CmdResult::Exit == result {
                     return CmdResult::Exit;
                 }
@@ -184,12 +448,44 @@ fn execute_cmd(
                     println!("vm not started, try `help`");
                     return CmdResult::Continue;
                 };
-                if let Err(err) = vm.run_next_instruction() {
-                    println!("vm unable to step: {err}");
-                    break 'eval_loop;
+                let (reason, executed) = vm.run(EVAL_BATCH_SIZE);
+                steps += executed;
+                match reason {
+                    StopReason::BudgetExhausted => {}
+                    StopReason::Halted if waits_for_interrupt(vm) => {
+                        drop(vm_ref);
+                        std::thread::yield_now();
+                        continue 'eval_loop;
+                    }
+                    StopReason::Halted => {
+                        println!("vm halted");
+                        break 'eval_loop;
+                    }
+                    StopReason::Breakpoint(address) => {
+                        println!("breakpoint hit at {address:#010X}");
+                        break 'eval_loop;
+                    }
+                    StopReason::Watchpoint(hit) => {
+                        print_watchpoint_hit(&hit);
+                        break 'eval_loop;
+                    }
+                    StopReason::Condition(address) => {
+                        println!("condition met at {address:#010X}");
+                        break 'eval_loop;
+                    }
+                    StopReason::Fault(err) => {
+                        println!("vm unable to step: {err}");
+                        break 'eval_loop;
+                    }
+                    StopReason::PcOutOfMemory => {
+                        println!(
+                            "vm unable to step: pc {:#010X} is out of memory",
+                            vm.register_value(&Register::ProgramCounter)
+                        );
+                        break 'eval_loop;
+                    }
                 }
                 drop(vm_ref);
-                steps += 1;
             }
             let now = Instant::now() - now;
             println!(
@@ -197,6 +493,313 @@ fn execute_cmd(
                 now.as_millis()
             );
         }
+        Some(cmd @ ("break" | "tbreak" | "delete")) => {
+            let mut vm = vm.lock().unwrap();
+            let Some(ref mut vm) = *vm else {
+                println!("vm not started, try `help`");
+                return CmdResult::Continue;
+            };
+            let address = buffer.next().and_then(|v| parse_integer(v).ok());
+            let Some(address) = address else {
+                println!("invalid address after `{cmd}`");
+                return CmdResult::Continue;
+            };
+            match cmd {
+                "break" => vm.add_breakpoint(address),
+                "tbreak" => vm.add_temporary_breakpoint(address),
+                _ => {
+                    if !vm.remove_breakpoint(address) {
+                        println!("no breakpoint at {address:#010X}");
+                    }
+                }
+            }
+        }
+        Some("breakpoints") => {
+            let vm = vm.lock().unwrap();
+            let Some(ref vm) = *vm else {
+                println!("vm not started, try `help`");
+                return CmdResult::Continue;
+            };
+            println!("[#] breakpoints:");
+            for (address, breakpoint) in vm.breakpoints() {
+                let temporary = if breakpoint.temporary {
+                    " (temporary)"
+                } else {
+                    ""
+                };
+                println!(
+                    "- {address:#010X}: hit {} times{temporary}",
+                    breakpoint.hit_count
+                );
+            }
+        }
+        Some(cmd @ "watch") => {
+            let mut vm = vm.lock().unwrap();
+            let Some(ref mut vm) = *vm else {
+                println!("vm not started, try `help`");
+                return CmdResult::Continue;
+            };
+            let kind = match buffer.next() {
+                Some("read") => WatchKind::Read,
+                Some("write") => WatchKind::Write,
+                Some("change") => WatchKind::Change,
+                Some(kind) => {
+                    println!("unrecognized watchpoint kind '{kind}'");
+                    return CmdResult::Continue;
+                }
+                None => {
+                    println!("missing watchpoint kind after `{cmd}` command");
+                    return CmdResult::Continue;
+                }
+            };
+            let start = buffer.next().and_then(|v| parse_integer(v).ok());
+            let Some(start) = start else {
+                println!("invalid watch start after `{cmd}`");
+                return CmdResult::Continue;
+            };
+            let stop = buffer.next().and_then(|v| parse_integer(v).ok());
+            let Some(stop) = stop else {
+                println!("invalid watch stop after `{cmd}`");
+                return CmdResult::Continue;
+            };
+            if start >= stop {
+                println!("start {start} cannot be >= stop {stop}; range is exclusive");
+                return CmdResult::Continue;
+            }
+            let id = vm.add_watchpoint(start..stop, kind);
+            println!("watchpoint {id} added");
+        }
+        Some(cmd @ "unwatch") => {
+            let mut vm = vm.lock().unwrap();
+            let Some(ref mut vm) = *vm else {
+                println!("vm not started, try `help`");
+                return CmdResult::Continue;
+            };
+            let id = buffer.next().and_then(|v| parse_integer(v).ok());
+            let Some(id) = id else {
+                println!("invalid watchpoint id after `{cmd}`");
+                return CmdResult::Continue;
+            };
+            if !vm.remove_watchpoint(id) {
+                println!("no watchpoint with id {id}");
+            }
+        }
+        Some("watchpoints") => {
+            let vm = vm.lock().unwrap();
+            let Some(ref vm) = *vm else {
+                println!("vm not started, try `help`");
+                return CmdResult::Continue;
+            };
+            println!("[#] watchpoints:");
+            for (id, watchpoint) in vm.watchpoints() {
+                println!(
+                    "- {id}: {:?} {:#010X}..{:#010X}",
+                    watchpoint.kind, watchpoint.range.start, watchpoint.range.end
+                );
+            }
+        }
+        Some(cmd @ "region") => {
+            let mut vm = vm.lock().unwrap();
+            let Some(ref mut vm) = *vm else {
+                println!("vm not started, try `help`");
+                return CmdResult::Continue;
+            };
+            let permissions = match buffer.next() {
+                Some("read-only") => Permissions::READ_ONLY,
+                Some("no-execute") => Permissions::NO_EXECUTE,
+                Some("unmapped") => Permissions::UNMAPPED,
+                Some(permissions) => {
+                    println!("unrecognized region permissions '{permissions}'");
+                    return CmdResult::Continue;
+                }
+                None => {
+                    println!("missing region permissions after `{cmd}` command");
+                    return CmdResult::Continue;
+                }
+            };
+            let start = buffer.next().and_then(|v| parse_integer(v).ok());
+            let Some(start) = start else {
+                println!("invalid region start after `{cmd}`");
+                return CmdResult::Continue;
+            };
+            let stop = buffer.next().and_then(|v| parse_integer(v).ok());
+            let Some(stop) = stop else {
+                println!("invalid region stop after `{cmd}`");
+                return CmdResult::Continue;
+            };
+            if start >= stop {
+                println!("start {start} cannot be >= stop {stop}; range is exclusive");
+                return CmdResult::Continue;
+            }
+            let id = vm.add_region(start..stop, permissions);
+            println!("region {id} added");
+        }
+        Some(cmd @ "unregion") => {
+            let mut vm = vm.lock().unwrap();
+            let Some(ref mut vm) = *vm else {
+                println!("vm not started, try `help`");
+                return CmdResult::Continue;
+            };
+            let id = buffer.next().and_then(|v| parse_integer(v).ok());
+            let Some(id) = id else {
+                println!("invalid region id after `{cmd}`");
+                return CmdResult::Continue;
+            };
+            if !vm.remove_region(id) {
+                println!("no region with id {id}");
+            }
+        }
+        Some("regions") => {
+            let vm = vm.lock().unwrap();
+            let Some(ref vm) = *vm else {
+                println!("vm not started, try `help`");
+                return CmdResult::Continue;
+            };
+            println!("[#] regions:");
+            for (id, region) in vm.regions() {
+                let Permissions {
+                    read,
+                    write,
+                    execute,
+                } = region.permissions;
+                let flag = |allowed, flag| if allowed { flag } else { '-' };
+                println!(
+                    "- {id}: {}{}{} {:#010X}..{:#010X}",
+                    flag(read, 'r'),
+                    flag(write, 'w'),
+                    flag(execute, 'x'),
+                    region.range.start,
+                    region.range.end
+                );
+            }
+        }
+        Some(cmd @ "profile") => {
+            let mut vm = vm.lock().unwrap();
+            let Some(ref mut vm) = *vm else {
+                println!("vm not started, try `help`");
+                return CmdResult::Continue;
+            };
+            match buffer.next() {
+                Some("start") => {
+                    vm.observer_mut().0 = Some(Profiler::new());
+                    println!("profiling started");
+                }
+                Some("stop") => match vm.observer_mut().0.take() {
+                    Some(_) => println!("profiling stopped"),
+                    None => println!("not profiling"),
+                },
+                Some("save") => {
+                    let Some(profiler) = &vm.observer().0 else {
+                        println!("not profiling, try `profile start`");
+                        return CmdResult::Continue;
+                    };
+                    let Some(file_name) = buffer.next() else {
+                        println!("missing file name after `{cmd} save` command");
+                        return CmdResult::Continue;
+                    };
+                    match std::fs::write(file_name, profiler.export()) {
+                        Ok(()) => println!("profile written to '{file_name}'"),
+                        Err(err) => println!("error writing profile to '{file_name}': {err}"),
+                    }
+                }
+                Some("report") => {
+                    let Some(profiler) = &vm.observer().0 else {
+                        println!("not profiling, try `profile start`");
+                        return CmdResult::Continue;
+                    };
+                    let count = match buffer.next().map(parse_integer::<usize>) {
+                        Some(Ok(count)) => count,
+                        Some(Err(err)) => {
+                            println!("invalid amount after `{cmd} report`: '{err}'");
+                            return CmdResult::Continue;
+                        }
+                        None => 10,
+                    };
+                    let symbols = match buffer.next().map(Symbols::from_file) {
+                        Some(Ok(symbols)) => Some(symbols),
+                        Some(Err(err)) => {
+                            println!("error reading symbols: {err}");
+                            return CmdResult::Continue;
+                        }
+                        None => None,
+                    };
+                    print_profile(profiler, count, symbols.as_ref());
+                }
+                Some(action) => println!("unrecognized action '{action}' after `{cmd}`"),
+                None => println!("missing start, stop, report or save after `{cmd}` command"),
+            }
+        }
+        Some(cmd @ "coverage") => {
+            let mut vm = vm.lock().unwrap();
+            let Some(ref mut vm) = *vm else {
+                println!("vm not started, try `help`");
+                return CmdResult::Continue;
+            };
+            match buffer.next() {
+                Some("start") => {
+                    vm.observer_mut().1 = Some(Coverage::new());
+                    println!("coverage started");
+                }
+                Some("stop") => match vm.observer_mut().1.take() {
+                    Some(_) => println!("coverage stopped"),
+                    None => println!("not recording coverage"),
+                },
+                Some("save") => {
+                    let Some(coverage) = &vm.observer().1 else {
+                        println!("not recording coverage, try `coverage start`");
+                        return CmdResult::Continue;
+                    };
+                    let Some(file_name) = buffer.next() else {
+                        println!("missing file name after `{cmd} save` command");
+                        return CmdResult::Continue;
+                    };
+                    match std::fs::write(file_name, coverage.export()) {
+                        Ok(()) => println!("coverage written to '{file_name}'"),
+                        Err(err) => println!("error writing coverage to '{file_name}': {err}"),
+                    }
+                }
+                Some("lcov") => {
+                    let Some(coverage) = &vm.observer().1 else {
+                        println!("not recording coverage, try `coverage start`");
+                        return CmdResult::Continue;
+                    };
+                    let (Some(file_name), Some(lines), Some(source)) =
+                        (buffer.next(), buffer.next(), buffer.next())
+                    else {
+                        println!("missing <path> <lines> <source> after `{cmd} lcov` command");
+                        return CmdResult::Continue;
+                    };
+                    let lines = match symbols::source_lines_from_file(lines) {
+                        Ok(lines) => lines,
+                        Err(err) => {
+                            println!("error reading lines: {err}");
+                            return CmdResult::Continue;
+                        }
+                    };
+                    match std::fs::write(file_name, coverage.lcov(source, lines)) {
+                        Ok(()) => println!("lcov written to '{file_name}'"),
+                        Err(err) => println!("error writing lcov to '{file_name}': {err}"),
+                    }
+                }
+                Some("report") => {
+                    let Some(coverage) = &vm.observer().1 else {
+                        println!("not recording coverage, try `coverage start`");
+                        return CmdResult::Continue;
+                    };
+                    let symbols = match buffer.next().map(Symbols::from_file) {
+                        Some(Ok(symbols)) => Some(symbols),
+                        Some(Err(err)) => {
+                            println!("error reading symbols: {err}");
+                            return CmdResult::Continue;
+                        }
+                        None => None,
+                    };
+                    print_coverage(coverage, symbols.as_ref());
+                }
+                Some(action) => println!("unrecognized action '{action}' after `{cmd}`"),
+                None => println!("missing start, stop, report, save or lcov after `{cmd}` command"),
+            }
+        }
         Some(cmd @ "registers") => {
             use vc2_vm::Register::*;
             let vm = vm.lock().unwrap();
@@ -222,6 +825,47 @@ fn execute_cmd(
                 "- pc: {}",
                 format_word(vm.register_value(&ProgramCounter), &format)
             );
+            if vm.config().profile == Profile::Stack {
+                println!("- sp: {}", format_word(vm.stack_pointer(), &format));
+            }
+            println!("[#] counters:");
+            println!("- instructions: {}", vm.instruction_count());
+            println!("- cycles: {}", vm.cycle_count());
+        }
+        Some(cmd @ "disassemble") => {
+            let vm = vm.lock().unwrap();
+            let Some(ref vm) = *vm else {
+                println!("vm not started, try `help`");
+                return CmdResult::Continue;
+            };
+            let start = match buffer.next() {
+                Some("pc") => Some(vm.register_value(&Register::ProgramCounter)),
+                start => start.and_then(|v| parse_integer(v).ok()),
+            };
+            let Some(start) = start else {
+                println!("invalid start after `{cmd}`");
+                return CmdResult::Continue;
+            };
+            let amount = buffer.next().and_then(|v| parse_integer::<usize>(v).ok());
+            let Some(amount) = amount else {
+                println!("invalid amount after `{cmd}`");
+                return CmdResult::Continue;
+            };
+
+            println!("[#] disassembly:");
+            let mut address: u32 = start;
+            for _ in 0..amount {
+                match vm.decode_at(address) {
+                    Ok((instruction, length)) => {
+                        println!("- {address:#010X}: {instruction}");
+                        address = address.wrapping_add(length as u32);
+                    }
+                    Err(err) => {
+                        println!("unable to decode instruction:\n  '{err}'");
+                        break;
+                    }
+                }
+            }
         }
         Some(cmd @ "memory") => {
             let vm = vm.lock().unwrap();
@@ -282,7 +926,7 @@ fn execute_cmd(
         None => {}
     };
     match buffer.next() {
-        Some("&&") => execute_cmd(vm, buffer, memory),
+        Some("&&") => execute_cmd(vm, buffer, config, engine),
         Some(cmd) => {
             println!("unrecognized trailing input '{cmd}'");
             CmdResult::Continue
@@ -306,10 +950,77 @@ struct MyOptions {
     )]
     memory: usize,
 
+    #[options(help = "allocate memory in pages when first written to, for large memory sizes")]
+    paged: bool,
+
+    #[options(
+        help = "shift mode of `shl` and `shr` (rotate, logical)",
+        default = "rotate",
+        parse(try_from_str = "parse_shift")
+    )]
+    shift: ShiftMode,
+
+    #[options(no_short, help = "don't add the carry/borrow flag in `add` and `sub`")]
+    no_carry_in: bool,
+
+    #[options(
+        no_short,
+        help = "byte order of words in memory (big, little)",
+        default = "big",
+        parse(try_from_str = "parse_endianness")
+    )]
+    endianness: Endianness,
+
+    #[options(
+        help = "architecture profile (base, stack), `stack` adds push, pop, call and ret",
+        default = "base",
+        parse(try_from_str = "parse_profile")
+    )]
+    profile: Profile,
+
+    #[options(
+        help = "execution engine (block, interpreter), `interpreter` decodes every instruction as it runs",
+        default = "block",
+        parse(try_from_str = "parse_engine")
+    )]
+    engine: ExecutionEngine,
+
     #[options(free, help = "starting input")]
     starting_input: String,
 }
 
+fn parse_engine(engine: &str) -> Result<ExecutionEngine, String> {
+    match engine {
+        "interpreter" => Ok(ExecutionEngine::Interpreter),
+        "block" => Ok(ExecutionEngine::BasicBlock),
+        _ => Err(format!("unknown engine '{engine}'")),
+    }
+}
+
+fn parse_shift(shift: &str) -> Result<ShiftMode, String> {
+    match shift {
+        "rotate" => Ok(ShiftMode::Rotate),
+        "logical" => Ok(ShiftMode::Logical),
+        _ => Err(format!("unknown shift mode '{shift}'")),
+    }
+}
+
+fn parse_endianness(endianness: &str) -> Result<Endianness, String> {
+    match endianness {
+        "big" => Ok(Endianness::Big),
+        "little" => Ok(Endianness::Little),
+        _ => Err(format!("unknown endianness '{endianness}'")),
+    }
+}
+
+fn parse_profile(profile: &str) -> Result<Profile, String> {
+    match profile {
+        "base" => Ok(Profile::Base),
+        "stack" => Ok(Profile::Stack),
+        _ => Err(format!("unknown profile '{profile}'")),
+    }
+}
+
 fn parse_number(number: &str) -> Result<usize, String> {
     if number.starts_with("0x") {
         usize::from_str_radix(&number[2..], 16).map_err(|e| e.to_string())
@@ -324,9 +1035,26 @@ fn main() -> Result<(), io::Error> {
     let MyOptions {
         log_level,
         memory,
+        paged,
+        shift,
+        no_carry_in,
+        endianness,
+        profile,
+        engine,
         starting_input,
         ..
     } = Options::parse_args_default_or_exit();
+    let backend = match paged {
+        true => MemoryBackend::Paged,
+        false => MemoryBackend::Flat,
+    };
+    let config = VmConfig::new()
+        .memory_size(memory)
+        .memory_backend(backend)
+        .shift(shift)
+        .carry_in(!no_carry_in)
+        .endianness(endianness)
+        .profile(profile);
     println!("[#] vc2-inspector started");
     let mut vm: Arc<Mutex<Option<Vm>>> = Arc::new(Mutex::new(None));
     SimpleLogger::new()
@@ -341,7 +1069,7 @@ fn main() -> Result<(), io::Error> {
 
     if starting_input.len() > 0 {
         let mut buffer = starting_input.split(' ').map(|v| v.trim());
-        if execute_cmd(&mut vm, &mut buffer, memory) == CmdResult::Exit {
+        if execute_cmd(&mut vm, &mut buffer, config, engine) == CmdResult::Exit {
             return Ok(());
         };
     }
@@ -354,7 +1082,7 @@ fn main() -> Result<(), io::Error> {
         stdin.read_line(&mut buffer)?;
 
         let mut buffer = buffer.split(' ').map(|v| v.trim());
-        if execute_cmd(&mut vm, &mut buffer, memory) == CmdResult::Exit {
+        if execute_cmd(&mut vm, &mut buffer, config, engine) == CmdResult::Exit {
             break Ok(());
         };
     }
@@ -3,28 +3,25 @@ use std::{
     thread::{self, JoinHandle},
 };
 
-pub const SCREEN_ENABLED_LOCATION: u32 = 0x2030;
-pub const SCREEN_VRAM_ADDRESS_LOCATION: u32 = 0x2034;
-pub const SCREEN_WIDTH_LOCATION: u32 = 0x2038;
-pub const SCREEN_HEIGHT_LOCATION: u32 = 0x203C;
-
-pub const KEYBOARD_ENABLED_LOCATION: u32 = 0x2020;
-pub const KEYCODE_LOCATION: u32 = 0x2028;
-pub const KEY_EVENT_CALLBACK_LOCATION: u32 = 0x202C;
-pub const KEY_EVENT_TYPE_LOCATION: u32 = 0x2024;
-
-pub const SCREEN_WIDTH: u32 = 120;
-pub const SCREEN_HEIGHT: u32 = 96;
-pub const SCREEN_VRAM_ADDRESS: u32 = 0x3000;
 pub const SCALE: u32 = 4;
 
 use sdl2::{event::Event, pixels::Color, rect::Rect, render::WindowCanvas};
-use vc2_vm::{Register, Vm};
+use vc2_vm::{ExternalInput, Register, Word, INTERRUPT_MASK_OFFSET};
 
-use crate::utils::sleep;
+use crate::{
+    devices::{
+        INTERRUPT_CONTROLLER_LOCATION, KEYBOARD_INTERRUPT, KEYCODE_LOCATION,
+        KEY_EVENT_CALLBACK_LOCATION, KEY_EVENT_TYPE_LOCATION, SCREEN_HEIGHT,
+        SCREEN_VRAM_ADDRESS_LOCATION, SCREEN_WIDTH,
+    },
+    utils::sleep,
+    Vm,
+};
 
 fn render_canvas(canvas: &mut WindowCanvas, vm: &Vm) -> Result<(), String> {
-    let vram_address = vm.memory_value(&SCREEN_VRAM_ADDRESS_LOCATION)?;
+    let vram_address = vm
+        .memory_value(&SCREEN_VRAM_ADDRESS_LOCATION)
+        .map_err(|err| err.to_string())?;
     for x in 0..SCREEN_WIDTH {
         for y in 0..SCREEN_HEIGHT {
             let pixel = vm
@@ -43,6 +40,36 @@ fn render_canvas(canvas: &mut WindowCanvas, vm: &Vm) -> Result<(), String> {
     Ok(())
 }
 
+/// writes the key event for the program and raises the keyboard interrupt,
+/// programs without an interrupt handler are jumped to the legacy callback instead
+fn key_event(vm: &mut Vm, event_type: Word, keycode: Word) {
+    vm.inject(ExternalInput::Memory {
+        address: KEYCODE_LOCATION,
+        value: keycode,
+    })
+    .unwrap();
+    vm.inject(ExternalInput::Memory {
+        address: KEY_EVENT_TYPE_LOCATION,
+        value: event_type,
+    })
+    .unwrap();
+    vm.inject(ExternalInput::Interrupt {
+        line: KEYBOARD_INTERRUPT,
+    })
+    .unwrap();
+    let interrupt_enabled = vm
+        .memory_value(&(INTERRUPT_CONTROLLER_LOCATION + INTERRUPT_MASK_OFFSET))
+        .is_ok_and(|mask| mask & (1 << KEYBOARD_INTERRUPT) != 0);
+    let callback = vm.memory_value(&KEY_EVENT_CALLBACK_LOCATION).unwrap();
+    if callback != 0 && !interrupt_enabled {
+        vm.inject(ExternalInput::Register {
+            register: Register::ProgramCounter,
+            value: callback,
+        })
+        .unwrap();
+    }
+}
+
 pub fn window(vm: Arc<Mutex<Option<Vm>>>) -> JoinHandle<()> {
     thread::spawn(move || {
         let sdl_context = sdl2::init().unwrap();
@@ -73,38 +100,20 @@ pub fn window(vm: Arc<Mutex<Option<Vm>>>) -> JoinHandle<()> {
                 for event in event_pump.poll_iter() {
                     match event {
                         Event::Quit { .. } => ::std::process::exit(1),
-                        Event::KeyDown { scancode, .. } => match scancode {
-                            Some(scancode) => {
-                                let Some(ref mut vm) = *vm else {
-                                    continue;
-                                };
-                                vm.set_memory_value(&KEYCODE_LOCATION, scancode as u32)
-                                    .unwrap();
-                                vm.set_memory_value(&KEY_EVENT_TYPE_LOCATION, 1).unwrap();
-                                let callback =
-                                    vm.memory_value(&KEY_EVENT_CALLBACK_LOCATION).unwrap();
-                                if callback != 0 {
-                                    vm.set_register_value(&Register::Flag, callback);
-                                }
-                            }
-                            None => println!("unrecognized key"),
-                        },
-                        Event::KeyUp { scancode, .. } => match scancode {
-                            Some(scancode) => {
-                                let Some(ref mut vm) = *vm else {
-                                    continue;
-                                };
-                                vm.set_memory_value(&KEYCODE_LOCATION, scancode as u32)
-                                    .unwrap();
-                                vm.set_memory_value(&KEY_EVENT_TYPE_LOCATION, 2).unwrap();
-                                let callback =
-                                    vm.memory_value(&KEY_EVENT_CALLBACK_LOCATION).unwrap();
-                                if callback != 0 {
-                                    vm.set_register_value(&Register::ProgramCounter, callback);
-                                }
-                            }
-                            None => println!("unrecognized key"),
-                        },
+                        Event::KeyDown { scancode, .. } | Event::KeyUp { scancode, .. } => {
+                            let Some(scancode) = scancode else {
+                                println!("unrecognized key");
+                                continue;
+                            };
+                            let Some(ref mut vm) = *vm else {
+                                continue;
+                            };
+                            let event_type = match event {
+                                Event::KeyDown { .. } => 1,
+                                _ => 2,
+                            };
+                            key_event(vm, event_type, scancode as u32);
+                        }
                         _ => {}
                     }
                 }
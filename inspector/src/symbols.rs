@@ -0,0 +1,89 @@
+use std::{fmt::Display, io};
+
+use vc2_vm::Word;
+
+use crate::utils::parse_integer;
+
+/// labels written by `vc2-assembler --symbols`, one `<address> <label>` per line
+pub struct Symbols {
+    /// ordered by address
+    labels: Vec<(Word, String)>,
+}
+
+pub enum SymbolsError {
+    Io(io::Error),
+    InvalidLine(usize),
+}
+
+impl Display for SymbolsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SymbolsError::Io(err) => write!(f, "{err}"),
+            SymbolsError::InvalidLine(line) => {
+                write!(f, "line {line} isn't an address followed by a value")
+            }
+        }
+    }
+}
+
+/// the `<address> <value>` lines of `file_name`, `parse` returns `None` for invalid values
+fn read_addresses<T>(
+    file_name: &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<Vec<(Word, T)>, SymbolsError> {
+    let text = std::fs::read_to_string(file_name).map_err(SymbolsError::Io)?;
+    let mut entries = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let address = parts.next().and_then(|v| parse_integer(v).ok());
+        let value = parts.next().and_then(&parse);
+        let (Some(address), Some(value), None) = (address, value, parts.next()) else {
+            return Err(SymbolsError::InvalidLine(index + 1));
+        };
+        entries.push((address, value));
+    }
+    Ok(entries)
+}
+
+/// source lines written by `vc2-assembler --lines`, one `<address> <line>` per instruction
+pub fn source_lines_from_file(file_name: &str) -> Result<Vec<(Word, usize)>, SymbolsError> {
+    read_addresses(file_name, |line| line.parse().ok())
+}
+
+impl Symbols {
+    pub fn from_file(file_name: &str) -> Result<Self, SymbolsError> {
+        let mut labels = read_addresses(file_name, |label| Some(label.to_string()))?;
+        labels.sort();
+        Ok(Self { labels })
+    }
+    /// the closest label at or before `address`, sub labels included when `sub_labels` is set
+    fn label_before(&self, address: Word, sub_labels: bool) -> Option<&(Word, String)> {
+        self.labels
+            .iter()
+            .take_while(|(label_address, _)| *label_address <= address)
+            .filter(|(_, label)| sub_labels || !label.contains('@'))
+            .last()
+    }
+    /// `address` as `<label>` or `<label>+<offset>`
+    pub fn describe(&self, address: Word) -> Option<String> {
+        let (label_address, label) = self.label_before(address, true)?;
+        match address - label_address {
+            0 => Some(label.clone()),
+            offset => Some(format!("{label}+{offset:#X}")),
+        }
+    }
+    /// the label at exactly `address`, e.g. for data
+    pub fn label_at(&self, address: Word) -> Option<&str> {
+        self.label_before(address, true)
+            .filter(|(label_address, _)| *label_address == address)
+            .map(|(_, label)| label.as_str())
+    }
+    /// the label `address` belongs to, ignoring sub labels
+    pub fn label(&self, address: Word) -> Option<&str> {
+        self.label_before(address, false)
+            .map(|(_, label)| label.as_str())
+    }
+}
@@ -19,6 +19,12 @@ impl FromStrRadix for u32 {
     }
 }
 
+impl FromStrRadix for u64 {
+    fn from_str_radix(value: &str, radix: u32) -> Result<Self, std::num::ParseIntError> {
+        Self::from_str_radix(value, radix)
+    }
+}
+
 impl FromStrRadix for usize {
     fn from_str_radix(value: &str, radix: u32) -> Result<Self, std::num::ParseIntError> {
         Self::from_str_radix(value, radix)
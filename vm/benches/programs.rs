@@ -0,0 +1,84 @@
+//! runs the bundled programs for a fixed amount of instructions,
+//! with the interpreter, with and without the instruction cache, and with the basic block engine
+//!
+//! `cargo bench -p vc2-vm`
+
+use std::time::{Duration, Instant};
+
+use vc2_assembler::{instructions::InstructionOrConstant, Assembler, Parser};
+use vc2_vm::{ExecutionEngine, StopReason, Vm, VmConfig};
+
+const MEMORY_SIZE: usize = 0x30000;
+const INSTRUCTIONS: u64 = 20_000_000;
+
+const SCREEN_ENABLED_LOCATION: u32 = 0x2030;
+const SCREEN_VRAM_ADDRESS_LOCATION: u32 = 0x2034;
+const SCREEN_WIDTH_LOCATION: u32 = 0x2038;
+const SCREEN_HEIGHT_LOCATION: u32 = 0x203C;
+
+const PROGRAMS: [(&str, &str); 3] = [
+    ("video", include_str!("../../programs/video.asm")),
+    ("dvd", include_str!("../../programs/dvd.asm")),
+    (
+        "double_buffer_render",
+        include_str!("../../programs/double_buffer_render.asm"),
+    ),
+];
+
+fn assemble(source: &str) -> Vec<u8> {
+    let nodes = Parser::new(source.as_bytes())
+        .parse()
+        .into_iter()
+        .collect::<Result<Vec<InstructionOrConstant>, _>>()
+        .expect("bundled programs are valid");
+    Assembler::new(&nodes).assemble()
+}
+
+fn vm(program: &[u8]) -> Vm {
+    let mut vm = Vm::new(program.to_vec(), VmConfig::new().memory_size(MEMORY_SIZE));
+    for (location, value) in [
+        (SCREEN_ENABLED_LOCATION, 1),
+        (SCREEN_VRAM_ADDRESS_LOCATION, 0x3000),
+        (SCREEN_WIDTH_LOCATION, 120),
+        (SCREEN_HEIGHT_LOCATION, 96),
+    ] {
+        vm.set_memory_value(&location, value).unwrap();
+    }
+    vm
+}
+
+fn bench(mut vm: Vm) -> Duration {
+    let now = Instant::now();
+    let (reason, executed) = vm.run(INSTRUCTIONS);
+    let elapsed = now.elapsed();
+    assert_eq!(
+        reason,
+        StopReason::BudgetExhausted,
+        "after {executed} instructions"
+    );
+    elapsed
+}
+
+fn main() {
+    println!("{INSTRUCTIONS} instructions per program:");
+    for (name, source) in PROGRAMS {
+        let program = assemble(source);
+
+        let mut uncached = vm(&program);
+        uncached.set_instruction_cache_enabled(false);
+        let uncached = bench(uncached);
+        let cached = bench(vm(&program));
+        let mut blocks = vm(&program);
+        blocks.set_execution_engine(ExecutionEngine::BasicBlock);
+        let blocks = bench(blocks);
+
+        println!(
+            "- {name}: uncached {}ms, cached {}ms ({:.2}x), blocks {}ms ({:.2}x the cached interpreter)",
+            uncached.as_millis(),
+            cached.as_millis(),
+            uncached.as_secs_f64() / cached.as_secs_f64(),
+            blocks.as_millis(),
+            cached.as_secs_f64() / blocks.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{
+    arch::Word,
+    config::ShiftMode,
+    vm::{Flag, MathOpVariant},
+};
+
+/// the value computed by an arithmetic or logic instruction and the flags it sets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AluResult {
+    pub(crate) value: Word,
+    /// the signed result didn't fit
+    pub(crate) overflow: bool,
+    /// the unsigned result didn't fit, a subtraction borrowed or a rotation carried a set bit around
+    pub(crate) carry: bool,
+}
+
+impl AluResult {
+    fn new(value: Word) -> Self {
+        Self {
+            value,
+            overflow: false,
+            carry: false,
+        }
+    }
+    /// `flags` with overflow, carry/borrow and equal, set when the value is zero, replaced,
+    /// less and below are only set by `cmp`
+    pub(crate) fn flags(&self, flags: Word) -> Word {
+        let updated = Flag::Overflow.bit() | Flag::CarryOrBorrow.bit() | Flag::Equal.bit();
+        let set = [
+            (Flag::Overflow, self.overflow),
+            (Flag::CarryOrBorrow, self.carry),
+            (Flag::Equal, self.value == 0),
+        ];
+        set.into_iter()
+            .filter(|(_, active)| *active)
+            .fold(flags & !updated, |flags, (flag, _)| flags | flag.bit())
+    }
+}
+
+pub(crate) fn add(value: Word, rhs: Word, carry: bool) -> AluResult {
+    let carry = Word::from(carry);
+    let (partial, carried) = value.overflowing_add(rhs);
+    let (result, carried_in) = partial.overflowing_add(carry);
+    let signed = i64::from(value as i32) + i64::from(rhs as i32) + i64::from(carry);
+    AluResult {
+        value: result,
+        overflow: i32::try_from(signed).is_err(),
+        carry: carried || carried_in,
+    }
+}
+
+pub(crate) fn sub(value: Word, rhs: Word, borrow: bool) -> AluResult {
+    let borrow = Word::from(borrow);
+    let (partial, borrowed) = value.overflowing_sub(rhs);
+    let (result, borrowed_in) = partial.overflowing_sub(borrow);
+    let signed = i64::from(value as i32) - i64::from(rhs as i32) - i64::from(borrow);
+    AluResult {
+        value: result,
+        overflow: i32::try_from(signed).is_err(),
+        carry: borrowed || borrowed_in,
+    }
+}
+
+pub(crate) fn not(value: Word) -> AluResult {
+    AluResult::new(!value)
+}
+
+/// `None` when dividing by zero
+pub(crate) fn math_op(
+    variant: &MathOpVariant,
+    value: Word,
+    rhs: Word,
+    shift: ShiftMode,
+) -> Option<AluResult> {
+    let result = match variant {
+        MathOpVariant::Or => AluResult::new(value | rhs),
+        MathOpVariant::And => AluResult::new(value & rhs),
+        MathOpVariant::Xor => AluResult::new(value ^ rhs),
+        // the carry is the last bit shifted out
+        MathOpVariant::Shl if shift == ShiftMode::Logical => AluResult {
+            carry: (1..=Word::BITS).contains(&rhs) && (value >> (Word::BITS - rhs)) & 1 != 0,
+            ..AluResult::new(value.checked_shl(rhs).unwrap_or(0))
+        },
+        MathOpVariant::Shr if shift == ShiftMode::Logical => AluResult {
+            carry: (1..=Word::BITS).contains(&rhs) && (value >> (rhs - 1)) & 1 != 0,
+            ..AluResult::new(value.checked_shr(rhs).unwrap_or(0))
+        },
+        MathOpVariant::Shl => {
+            let result = value.rotate_left(rhs);
+            AluResult {
+                carry: !rhs.is_multiple_of(Word::BITS) && result & 1 != 0,
+                ..AluResult::new(result)
+            }
+        }
+        MathOpVariant::Shr => {
+            let result = value.rotate_right(rhs);
+            AluResult {
+                carry: !rhs.is_multiple_of(Word::BITS) && result >> (Word::BITS - 1) != 0,
+                ..AluResult::new(result)
+            }
+        }
+        MathOpVariant::Mul => {
+            let (result, overflowed) = value.overflowing_mul(rhs);
+            AluResult {
+                value: result,
+                overflow: overflowed,
+                carry: overflowed,
+            }
+        }
+        MathOpVariant::IMul => {
+            let (result, overflowed) = (value as i32).overflowing_mul(rhs as i32);
+            AluResult {
+                value: result as Word,
+                overflow: overflowed,
+                carry: overflowed,
+            }
+        }
+        MathOpVariant::Div => AluResult::new(value.checked_div(rhs)?),
+        MathOpVariant::IDiv => {
+            if rhs == 0 {
+                return None;
+            }
+            let (result, overflowed) = (value as i32).overflowing_div(rhs as i32);
+            AluResult {
+                overflow: overflowed,
+                ..AluResult::new(result as Word)
+            }
+        }
+        MathOpVariant::Rem => AluResult::new(value.checked_rem(rhs)?),
+    };
+    Some(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{add, math_op, not, sub, AluResult};
+    use crate::{arch::Word, config::ShiftMode, vm::MathOpVariant};
+
+    const MIN: Word = i32::MIN as Word;
+    const MAX: Word = i32::MAX as Word;
+    const NEG_1: Word = -1i32 as Word;
+    const NEG_3: Word = -3i32 as Word;
+
+    type Op<'a> = &'a dyn Fn(Word, Word) -> AluResult;
+
+    #[test]
+    fn flag_results() {
+        use MathOpVariant::*;
+        let op =
+            |variant| move |value, rhs| math_op(&variant, value, rhs, ShiftMode::Rotate).unwrap();
+        let logical =
+            |variant| move |value, rhs| math_op(&variant, value, rhs, ShiftMode::Logical).unwrap();
+        let (adc, sbb) = (|a, b| add(a, b, true), |a, b| sub(a, b, true));
+        let (add, sub) = (|a, b| add(a, b, false), |a, b| sub(a, b, false));
+        let not = |a, _| not(a);
+        // name, op, operands, value, overflow and carry/borrow
+        let cases: [(&str, Op, Word, Word, Word, bool, bool); 40] = [
+            ("add", &add, 1, 2, 3, false, false),
+            ("add", &add, NEG_1, 1, 0, false, true),
+            ("add", &add, MAX, 1, MIN, true, false),
+            ("add", &add, MIN, NEG_1, MAX, true, true),
+            ("adc", &adc, 1, 2, 4, false, false),
+            ("adc", &adc, NEG_1, NEG_1, NEG_1, false, true),
+            ("adc", &adc, 0, NEG_1, 0, false, true),
+            ("adc", &adc, MAX, 0, MIN, true, false),
+            ("sub", &sub, 3, 2, 1, false, false),
+            ("sub", &sub, 2, 2, 0, false, false),
+            ("sub", &sub, 2, 3, NEG_1, false, true),
+            ("sub", &sub, MIN, 1, MAX, true, false),
+            ("sub", &sub, MAX, NEG_1, MIN, true, true),
+            ("sbb", &sbb, 3, 2, 0, false, false),
+            ("sbb", &sbb, 0, NEG_1, 0, false, true),
+            ("sbb", &sbb, MIN, 0, MAX, true, false),
+            ("not", &not, NEG_1, 0, 0, false, false),
+            ("or", &op(Or), 0b01, 0b10, 0b11, false, false),
+            ("and", &op(And), 0b01, 0b10, 0, false, false),
+            ("xor", &op(Xor), NEG_1, NEG_1, 0, false, false),
+            ("shl", &op(Shl), MIN | 1, 1, 0b11, false, true),
+            ("shl", &op(Shl), 1, 1, 0b10, false, false),
+            ("shl", &op(Shl), 1, 32, 1, false, false),
+            ("shr", &op(Shr), 0b11, 1, MIN | 1, false, true),
+            ("shr", &op(Shr), 0b10, 1, 1, false, false),
+            ("lsl", &logical(Shl), MIN | 1, 1, 0b10, false, true),
+            ("lsl", &logical(Shl), 0b11, 31, MIN, false, true),
+            ("lsl", &logical(Shl), 1, 32, 0, false, true),
+            ("lsr", &logical(Shr), 0b11, 1, 1, false, true),
+            ("lsr", &logical(Shr), MIN, 32, 0, false, true),
+            ("lsr", &logical(Shr), NEG_1, 33, 0, false, false),
+            ("mul", &op(Mul), 3, 4, 12, false, false),
+            ("mul", &op(Mul), 0x10000, 0x10000, 0, true, true),
+            ("imul", &op(IMul), 3, NEG_1, NEG_3, false, false),
+            ("imul", &op(IMul), MIN, NEG_1, MIN, true, true),
+            ("div", &op(Div), 7, 2, 3, false, false),
+            ("div", &op(Div), 1, 2, 0, false, false),
+            ("idiv", &op(IDiv), -7i32 as Word, 2, NEG_3, false, false),
+            ("idiv", &op(IDiv), MIN, NEG_1, MIN, true, false),
+            ("rem", &op(Rem), 7, 2, 1, false, false),
+        ];
+        for (name, op, a, b, value, overflow, carry) in cases {
+            let expected = AluResult {
+                value,
+                overflow,
+                carry,
+            };
+            assert_eq!(op(a, b), expected, "{name} {a:#X}, {b:#X}");
+        }
+    }
+
+    #[test]
+    fn flags_replace_overflow_carry_and_equal() {
+        let less_below = 0b11000;
+        let zero = AluResult {
+            value: 0,
+            overflow: false,
+            carry: true,
+        };
+        assert_eq!(zero.flags(less_below | 0b1), less_below | 0b110);
+        let overflowed = AluResult {
+            value: 1,
+            overflow: true,
+            carry: false,
+        };
+        assert_eq!(overflowed.flags(0b110), 0b1);
+    }
+}
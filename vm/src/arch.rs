@@ -1 +1,10 @@
 pub type Word = u32;
+
+/// bytes in a [`Word`]
+pub(crate) const WORD_SIZE: Word = Word::BITS / 8;
+
+/// the initial stack pointer, the end of memory or the last word when the end doesn't fit in a
+/// [`Word`]
+pub(crate) fn stack_top(memory_size: usize) -> Word {
+    Word::try_from(memory_size).unwrap_or(Word::MAX - (WORD_SIZE - 1))
+}
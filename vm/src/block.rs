@@ -0,0 +1,834 @@
+use std::ops::RangeInclusive;
+
+use crate::{
+    alu::{self, AluResult},
+    arch::Word,
+    error::VmError,
+    observer::VmObserver,
+    region::AccessKind,
+    vm::{
+        CallConfig, ConditionalJmpConfig, Config, Flag, Instruction, JmpConfig, JmpVariant,
+        MathOpVariant, NotConfig, PopConfig, PushConfig, Register, StepOutcome, Vm,
+    },
+};
+
+const PAGE_BITS: u32 = 12;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+const MAX_BLOCK_LENGTH: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecutionEngine {
+    /// decodes and executes one instruction at a time
+    #[default]
+    Interpreter,
+    /// decodes straight-line runs of instructions once into blocks of ops,
+    /// with their operands, alu operations, cycles and immediate jump targets resolved,
+    /// and runs them back to back without fetching or matching on the encoding again,
+    /// code which writes to itself is left to the interpreter
+    BasicBlock,
+}
+
+/// where an op reads or writes a word, split out of the instruction's config
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Operand {
+    Register(Register),
+    Immediate(Word),
+    RegisterAddress(Register),
+    ImmediateAddress(Word),
+}
+
+impl Operand {
+    /// `(destination, source)`
+    fn pair(config: Config) -> (Self, Self) {
+        match config {
+            Config::RegisterFromRegister(destination, source) => {
+                (Self::Register(destination), Self::Register(source))
+            }
+            Config::RegisterFromImmediate(destination, source) => {
+                (Self::Register(destination), Self::Immediate(source))
+            }
+            Config::RegisterFromRegisterAddress(destination, source) => {
+                (Self::Register(destination), Self::RegisterAddress(source))
+            }
+            Config::RegisterFromImmediateAddress(destination, source) => {
+                (Self::Register(destination), Self::ImmediateAddress(source))
+            }
+            Config::RegisterAddressFromRegister(destination, source) => {
+                (Self::RegisterAddress(destination), Self::Register(source))
+            }
+            Config::RegisterAddressFromImmediate(destination, source) => {
+                (Self::RegisterAddress(destination), Self::Immediate(source))
+            }
+            Config::ImmediateAddressFromRegister(destination, source) => {
+                (Self::ImmediateAddress(destination), Self::Register(source))
+            }
+            Config::ImmediateAddressFromImmediate(destination, source) => {
+                (Self::ImmediateAddress(destination), Self::Immediate(source))
+            }
+            Config::ImmediateFromImmediate(destination, source) => {
+                (Self::Immediate(destination), Self::Immediate(source))
+            }
+            Config::ImmediateFromRegister(destination, source) => {
+                (Self::Immediate(destination), Self::Register(source))
+            }
+        }
+    }
+    /// `(target, condition)`
+    fn branch(config: ConditionalJmpConfig) -> (Self, Self) {
+        match config {
+            ConditionalJmpConfig::RegisterFromRegister(target, condition) => {
+                (Self::Register(target), Self::Register(condition))
+            }
+            ConditionalJmpConfig::RegisterFromImmediate(target, condition) => {
+                (Self::Register(target), Self::Immediate(condition))
+            }
+            ConditionalJmpConfig::RegisterFromRegisterAddress(target, condition) => {
+                (Self::Register(target), Self::RegisterAddress(condition))
+            }
+            ConditionalJmpConfig::RegisterFromImmediateAddress(target, condition) => {
+                (Self::Register(target), Self::ImmediateAddress(condition))
+            }
+            ConditionalJmpConfig::ImmediateFromRegister(target, condition) => {
+                (Self::Immediate(target), Self::Register(condition))
+            }
+            ConditionalJmpConfig::ImmediateFromImmediate(target, condition) => {
+                (Self::Immediate(target), Self::Immediate(condition))
+            }
+            ConditionalJmpConfig::ImmediateFromRegisterAddress(target, condition) => {
+                (Self::Immediate(target), Self::RegisterAddress(condition))
+            }
+            ConditionalJmpConfig::ImmediateFromImmediateAddress(target, condition) => {
+                (Self::Immediate(target), Self::ImmediateAddress(condition))
+            }
+            ConditionalJmpConfig::RegisterAddressFromRegister(target, condition) => {
+                (Self::RegisterAddress(target), Self::Register(condition))
+            }
+            ConditionalJmpConfig::RegisterAddressFromImmediate(target, condition) => {
+                (Self::RegisterAddress(target), Self::Immediate(condition))
+            }
+            ConditionalJmpConfig::ImmediateAddressFromRegister(target, condition) => {
+                (Self::ImmediateAddress(target), Self::Register(condition))
+            }
+            ConditionalJmpConfig::ImmediateAddressFromImmediate(target, condition) => {
+                (Self::ImmediateAddress(target), Self::Immediate(condition))
+            }
+        }
+    }
+    fn register(&self) -> Option<Register> {
+        match self {
+            Operand::Register(register) => Some(*register),
+            _ => None,
+        }
+    }
+}
+
+impl From<JmpConfig> for Operand {
+    fn from(config: JmpConfig) -> Self {
+        match config {
+            JmpConfig::Register(register) => Self::Register(register),
+            JmpConfig::Immediate(immediate) => Self::Immediate(immediate),
+            JmpConfig::RegisterAddress(register) => Self::RegisterAddress(register),
+            JmpConfig::ImmediateAddress(immediate) => Self::ImmediateAddress(immediate),
+        }
+    }
+}
+
+impl From<NotConfig> for Operand {
+    fn from(config: NotConfig) -> Self {
+        match config {
+            NotConfig::Register(register) => Self::Register(register),
+            NotConfig::RegisterAddress(register) => Self::RegisterAddress(register),
+            NotConfig::ImmediateAddress(immediate) => Self::ImmediateAddress(immediate),
+        }
+    }
+}
+
+impl From<PushConfig> for Operand {
+    fn from(config: PushConfig) -> Self {
+        match config {
+            PushConfig::Register(register) => Self::Register(register),
+            PushConfig::Immediate(immediate) => Self::Immediate(immediate),
+            PushConfig::RegisterAddress(register) => Self::RegisterAddress(register),
+            PushConfig::ImmediateAddress(immediate) => Self::ImmediateAddress(immediate),
+        }
+    }
+}
+
+impl From<PopConfig> for Operand {
+    fn from(config: PopConfig) -> Self {
+        match config {
+            PopConfig::Register(register) => Self::Register(register),
+            PopConfig::RegisterAddress(register) => Self::RegisterAddress(register),
+            PopConfig::ImmediateAddress(immediate) => Self::ImmediateAddress(immediate),
+        }
+    }
+}
+
+impl From<CallConfig> for Operand {
+    fn from(config: CallConfig) -> Self {
+        match config {
+            CallConfig::Register(register) => Self::Register(register),
+            CallConfig::Immediate(immediate) => Self::Immediate(immediate),
+            CallConfig::RegisterAddress(register) => Self::RegisterAddress(register),
+            CallConfig::ImmediateAddress(immediate) => Self::ImmediateAddress(immediate),
+        }
+    }
+}
+
+/// what an instruction does, with the operation picked and its operands resolved
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Op {
+    Nop,
+    Hlt,
+    Mov {
+        destination: Operand,
+        source: Operand,
+    },
+    Not(Operand),
+    Math {
+        variant: MathOpVariant,
+        destination: Operand,
+        source: Operand,
+    },
+    /// `add` and `sub`
+    WithCarry {
+        operation: fn(Word, Word, bool) -> AluResult,
+        destination: Operand,
+        source: Operand,
+    },
+    Cmp {
+        destination: Operand,
+        source: Operand,
+    },
+    /// immediate relative targets are made absolute
+    Jmp {
+        target: Operand,
+        variant: JmpVariant,
+    },
+    /// `jz` when `jump_if_zero`, `jnz` otherwise
+    Branch {
+        target: Operand,
+        condition: Operand,
+        variant: JmpVariant,
+        jump_if_zero: bool,
+    },
+    Push(Operand),
+    Pop(Operand),
+    Call(Operand),
+    Ret,
+}
+
+/// relative jumps to an immediate always land on the same address
+fn resolve_target(location: Word, target: Operand, variant: JmpVariant) -> (Operand, JmpVariant) {
+    match (target, variant) {
+        (Operand::Immediate(target), JmpVariant::Relative) => (
+            Operand::Immediate(variant.destination(location, target)),
+            JmpVariant::Absolute,
+        ),
+        _ => (target, variant),
+    }
+}
+
+impl Op {
+    fn lower(location: Word, instruction: Instruction) -> Self {
+        let math = |variant, config| {
+            let (destination, source) = Operand::pair(config);
+            Op::Math {
+                variant,
+                destination,
+                source,
+            }
+        };
+        let with_carry = |operation, config| {
+            let (destination, source) = Operand::pair(config);
+            Op::WithCarry {
+                operation,
+                destination,
+                source,
+            }
+        };
+        let branch = |config, variant, jump_if_zero| {
+            let (target, condition) = Operand::branch(config);
+            let (target, variant) = resolve_target(location, target, variant);
+            Op::Branch {
+                target,
+                condition,
+                variant,
+                jump_if_zero,
+            }
+        };
+        match instruction {
+            Instruction::Nop => Op::Nop,
+            Instruction::Hlt => Op::Hlt,
+            Instruction::Mov(config) => {
+                let (destination, source) = Operand::pair(config);
+                Op::Mov {
+                    destination,
+                    source,
+                }
+            }
+            Instruction::Not(config) => Op::Not(config.into()),
+            Instruction::Or(config) => math(MathOpVariant::Or, config),
+            Instruction::And(config) => math(MathOpVariant::And, config),
+            Instruction::Xor(config) => math(MathOpVariant::Xor, config),
+            Instruction::Shl(config) => math(MathOpVariant::Shl, config),
+            Instruction::Shr(config) => math(MathOpVariant::Shr, config),
+            Instruction::Add(config) => with_carry(alu::add, config),
+            Instruction::Sub(config) => with_carry(alu::sub, config),
+            Instruction::Mul(config) => math(MathOpVariant::Mul, config),
+            Instruction::IMul(config) => math(MathOpVariant::IMul, config),
+            Instruction::Div(config) => math(MathOpVariant::Div, config),
+            Instruction::IDiv(config) => math(MathOpVariant::IDiv, config),
+            Instruction::Rem(config) => math(MathOpVariant::Rem, config),
+            Instruction::Cmp(config) => {
+                let (destination, source) = Operand::pair(config);
+                Op::Cmp {
+                    destination,
+                    source,
+                }
+            }
+            Instruction::Jmp(config, variant) => {
+                let (target, variant) = resolve_target(location, config.into(), variant);
+                Op::Jmp { target, variant }
+            }
+            Instruction::Jz(config, variant) => branch(config, variant, true),
+            Instruction::Jnz(config, variant) => branch(config, variant, false),
+            Instruction::Push(config) => Op::Push(config.into()),
+            Instruction::Pop(config) => Op::Pop(config.into()),
+            Instruction::Call(config) => Op::Call(config.into()),
+            Instruction::Ret => Op::Ret,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BlockOp {
+    pub(crate) location: Word,
+    pub(crate) length: u8,
+    pub(crate) cycles: u64,
+    pub(crate) op: Op,
+    /// passed to observers
+    pub(crate) instruction: Instruction,
+}
+
+struct Page {
+    /// ids of the blocks starting at each address
+    blocks: Box<[Option<u32>; PAGE_SIZE]>,
+    /// start addresses of the blocks with code in this page
+    starts: Vec<Word>,
+    /// whether decoded code in this page was written to
+    self_modifying: bool,
+}
+
+impl Page {
+    fn new() -> Self {
+        Self {
+            blocks: vec![None; PAGE_SIZE]
+                .into_boxed_slice()
+                .try_into()
+                .expect("vec has page size"),
+            starts: Vec::new(),
+            self_modifying: false,
+        }
+    }
+}
+
+/// blocks of decoded instructions by start address,
+/// split into lazily allocated pages like the instruction cache
+pub(crate) struct BlockCache {
+    pages: Vec<Option<Box<Page>>>,
+    /// blocks by id, dropped blocks are left empty until their id is reused
+    blocks: Vec<Box<[BlockOp]>>,
+    free_ids: Vec<u32>,
+}
+
+impl BlockCache {
+    fn new(memory_size: usize) -> Self {
+        let page_count = memory_size.div_ceil(PAGE_SIZE);
+        Self {
+            pages: (0..page_count).map(|_| None).collect(),
+            blocks: Vec::new(),
+            free_ids: Vec::new(),
+        }
+    }
+    fn page_index(address: Word) -> usize {
+        (address >> PAGE_BITS) as usize
+    }
+    fn entry_index(address: Word) -> usize {
+        address as usize & (PAGE_SIZE - 1)
+    }
+    fn get(&self, address: Word) -> Option<u32> {
+        self.pages.get(Self::page_index(address))?.as_ref()?.blocks[Self::entry_index(address)]
+    }
+    fn is_self_modifying(&self, address: Word) -> bool {
+        self.pages
+            .get(Self::page_index(address))
+            .and_then(Option::as_ref)
+            .is_some_and(|page| page.self_modifying)
+    }
+    fn pages_of(block: &[BlockOp]) -> RangeInclusive<usize> {
+        let first = block.first().expect("blocks aren't empty");
+        let last = block.last().expect("blocks aren't empty");
+        let end = last.location + Word::from(last.length) - 1;
+        Self::page_index(first.location)..=Self::page_index(end)
+    }
+    fn insert(&mut self, address: Word, block: Box<[BlockOp]>) -> u32 {
+        for page in &mut self.pages[Self::pages_of(&block)] {
+            page.get_or_insert_with(|| Box::new(Page::new()))
+                .starts
+                .push(address);
+        }
+        let id = match self.free_ids.pop() {
+            Some(id) => {
+                self.blocks[id as usize] = block;
+                id
+            }
+            None => {
+                self.blocks.push(block);
+                (self.blocks.len() - 1)
+                    .try_into()
+                    .expect("less blocks than addresses")
+            }
+        };
+        let page = self.pages[Self::page_index(address)]
+            .as_mut()
+            .expect("page allocated above");
+        page.blocks[Self::entry_index(address)] = Some(id);
+        id
+    }
+    fn remove(&mut self, address: Word) {
+        let Some(page) = &mut self.pages[Self::page_index(address)] else {
+            return;
+        };
+        let Some(id) = page.blocks[Self::entry_index(address)].take() else {
+            return;
+        };
+        let block = std::mem::take(&mut self.blocks[id as usize]);
+        for page in self.pages[Self::pages_of(&block)].iter_mut().flatten() {
+            page.starts.retain(|start| *start != address);
+        }
+        self.free_ids.push(id);
+    }
+    /// drops every block overlapping the word written at `address`,
+    /// marking the written pages as self-modifying if there were any
+    pub(crate) fn invalidate(&mut self, address: Word) {
+        let written = u64::from(address)..u64::from(address) + 4;
+        let first_page = Self::page_index(address);
+        let last_page = Self::page_index(address.saturating_add(3));
+        for page_index in first_page..=last_page {
+            let Some(Some(page)) = self.pages.get(page_index) else {
+                continue;
+            };
+            let overlapping: Vec<Word> = page
+                .starts
+                .iter()
+                .copied()
+                .filter(|start| {
+                    let id = self.get(*start).expect("starts point to blocks");
+                    let last = self.blocks[id as usize]
+                        .last()
+                        .expect("blocks aren't empty");
+                    let end = u64::from(last.location) + u64::from(last.length);
+                    u64::from(*start) < written.end && written.start < end
+                })
+                .collect();
+            if overlapping.is_empty() {
+                continue;
+            }
+            for start in overlapping {
+                self.remove(start);
+            }
+            self.pages[page_index]
+                .as_mut()
+                .expect("page has blocks")
+                .self_modifying = true;
+        }
+    }
+}
+
+fn ends_block(instruction: &Instruction) -> bool {
+    match instruction {
+        Instruction::Hlt
+        | Instruction::Jmp(..)
+        | Instruction::Jz(..)
+        | Instruction::Jnz(..)
+        | Instruction::Call(_)
+        | Instruction::Ret => true,
+        Instruction::Not(config) => *config == NotConfig::Register(Register::ProgramCounter),
+        Instruction::Pop(config) => *config == PopConfig::Register(Register::ProgramCounter),
+        Instruction::Mov(config)
+        | Instruction::Or(config)
+        | Instruction::And(config)
+        | Instruction::Xor(config)
+        | Instruction::Shl(config)
+        | Instruction::Shr(config)
+        | Instruction::Add(config)
+        | Instruction::Sub(config)
+        | Instruction::Mul(config)
+        | Instruction::IMul(config)
+        | Instruction::Div(config)
+        | Instruction::IDiv(config)
+        | Instruction::Rem(config) => matches!(
+            config,
+            Config::RegisterFromRegister(Register::ProgramCounter, _)
+                | Config::RegisterFromImmediate(Register::ProgramCounter, _)
+                | Config::RegisterFromRegisterAddress(Register::ProgramCounter, _)
+                | Config::RegisterFromImmediateAddress(Register::ProgramCounter, _)
+        ),
+        Instruction::Nop | Instruction::Cmp(_) | Instruction::Push(_) => false,
+    }
+}
+
+impl<O: VmObserver> Vm<O> {
+    /// the interpreter is used by default
+    pub fn set_execution_engine(&mut self, engine: ExecutionEngine) {
+        self.block_cache = match engine {
+            ExecutionEngine::Interpreter => None,
+            ExecutionEngine::BasicBlock => Some(BlockCache::new(self.memory_len())),
+        };
+    }
+    pub fn execution_engine(&self) -> ExecutionEngine {
+        match self.block_cache {
+            Some(_) => ExecutionEngine::BasicBlock,
+            None => ExecutionEngine::Interpreter,
+        }
+    }
+    /// id of the block starting at `address`, decoding it if needed,
+    /// `None` when the interpreter should be used instead
+    pub(crate) fn block_at(&mut self, address: Word) -> Option<u32> {
+        // replayed inputs and interrupts are handled by the interpreter
+        if self.replay.is_some() || self.interrupts.is_deliverable() {
+            return None;
+        }
+        let blocks = self.block_cache.as_ref()?;
+        if let Some(id) = blocks.get(address) {
+            return Some(id);
+        }
+        if blocks.is_self_modifying(address) {
+            return None;
+        }
+        let block = self.decode_block(address)?;
+        let blocks = self.block_cache.as_mut().expect("checked above");
+        Some(blocks.insert(address, block))
+    }
+    fn decode_block(&self, address: Word) -> Option<Box<[BlockOp]>> {
+        let blocks = self.block_cache.as_ref()?;
+        let mut ops = Vec::new();
+        let mut location = address;
+        while ops.len() < MAX_BLOCK_LENGTH {
+            if location < address || blocks.is_self_modifying(location) {
+                break;
+            }
+            let Ok((instruction, length)) = self.decode_at(location) else {
+                break;
+            };
+            // faults are raised by the interpreter
+            if !self.regions.is_empty()
+                && !self.is_allowed(location, length as Word, AccessKind::Execute)
+            {
+                break;
+            }
+            ops.push(BlockOp {
+                location,
+                length: length
+                    .try_into()
+                    .expect("instructions are at most 10 bytes"),
+                cycles: instruction.cycles(),
+                op: Op::lower(location, instruction),
+                instruction,
+            });
+            if ends_block(&instruction) {
+                break;
+            }
+            location = location.wrapping_add(length as Word);
+        }
+        (!ops.is_empty()).then(|| ops.into())
+    }
+    /// the op at `index` in block `id`, `None` past the end or once the block was dropped
+    pub(crate) fn block_op(&self, id: u32, index: usize) -> Option<BlockOp> {
+        self.block_cache.as_ref()?.blocks[id as usize]
+            .get(index)
+            .copied()
+    }
+    /// runs the op at `index` in a block, only the first op needs every check of
+    /// [`Vm::run_next_instruction`] since the following ones are in memory and can't follow a `hlt`,
+    /// each op behaves exactly like the instruction it was lowered from
+    pub(crate) fn run_block_op(
+        &mut self,
+        op: &BlockOp,
+        index: usize,
+    ) -> Result<StepOutcome, VmError> {
+        if index == 0 {
+            if let Some(outcome) = self.prepare_step(op.location)? {
+                return Ok(outcome);
+            }
+        } else if !self.breakpoints.is_empty() && self.hit_breakpoint(op.location) {
+            return Ok(StepOutcome::Breakpoint(op.location));
+        }
+        // the whole block was checked against the regions when it was decoded
+        let next_location =
+            self.start_instruction(op.location, &op.instruction, op.length.into(), op.cycles);
+        match op.op {
+            Op::Nop => (),
+            Op::Hlt => {
+                self.hlt_location = Some(next_location);
+                return Ok(StepOutcome::Halted);
+            }
+            Op::Mov {
+                destination,
+                source,
+            } => {
+                let value = self.operand_value(source)?;
+                self.set_operand_value(destination, value)?;
+            }
+            Op::Not(operand) => {
+                let mut result = None;
+                self.modify_operand(operand, Operand::Immediate(0), |value, _| {
+                    let value = alu::not(value);
+                    result = Some(value);
+                    Ok(value.value)
+                })?;
+                let result = result.expect("modify_operand runs the action");
+                self.set_result_flags(operand.register(), result);
+            }
+            Op::Math {
+                variant,
+                destination,
+                source,
+            } => {
+                let pc = self.instruction_location;
+                let shift = self.config().shift;
+                let mut result = None;
+                self.modify_operand(destination, source, |destination, source| {
+                    let value = alu::math_op(&variant, destination, source, shift)
+                        .ok_or(VmError::DivideByZero { pc })?;
+                    result = Some(value);
+                    Ok(value.value)
+                })?;
+                let result = result.expect("modify_operand runs the action");
+                self.set_result_flags(destination.register(), result);
+            }
+            Op::WithCarry {
+                operation,
+                destination,
+                source,
+            } => {
+                let carry = self.config().carry_in
+                    && Flag::CarryOrBorrow.is_active(self.register_value(&Register::Flag));
+                let mut result = None;
+                self.modify_operand(destination, source, |destination, source| {
+                    let value = operation(destination, source, carry);
+                    result = Some(value);
+                    Ok(value.value)
+                })?;
+                let result = result.expect("modify_operand runs the action");
+                self.set_result_flags(destination.register(), result);
+            }
+            Op::Cmp {
+                destination,
+                source,
+            } => {
+                let mut flags = 0;
+                // like the interpreter, the destination is written back unchanged
+                self.modify_operand(destination, source, |destination, source| {
+                    flags = compare(destination, source);
+                    Ok(destination)
+                })?;
+                self.set_register_value(&Register::Flag, flags);
+            }
+            Op::Jmp { target, variant } => {
+                let target = self.operand_value(target)?;
+                let destination = variant.destination(op.location, target);
+                self.set_register_value(&Register::ProgramCounter, destination);
+            }
+            Op::Branch {
+                target,
+                condition,
+                variant,
+                jump_if_zero,
+            } => {
+                let condition = self.operand_value(condition)?;
+                let target = self.operand_value(target)?;
+                let taken = (condition == 0) == jump_if_zero;
+                self.observer.on_branch(op.location, taken);
+                if taken {
+                    let destination = variant.destination(op.location, target);
+                    self.set_register_value(&Register::ProgramCounter, destination);
+                }
+            }
+            Op::Push(operand) => {
+                let value = self.operand_value(operand)?;
+                self.push(value)?;
+            }
+            Op::Pop(operand) => {
+                let stack_pointer = self.stack_pointer();
+                let value = self.load(&stack_pointer)?;
+                self.set_operand_value(operand, value)?;
+                self.set_stack_pointer(stack_pointer.wrapping_add(4));
+            }
+            Op::Call(operand) => {
+                let destination = self.operand_value(operand)?;
+                self.push(next_location)?;
+                self.set_register_value(&Register::ProgramCounter, destination);
+            }
+            Op::Ret => {
+                let destination = self.pop()?;
+                self.set_register_value(&Register::ProgramCounter, destination);
+            }
+        }
+        Ok(self.finish_instruction(op.location, next_location))
+    }
+    fn operand_value(&mut self, operand: Operand) -> Result<Word, VmError> {
+        match operand {
+            Operand::Register(register) => Ok(self.register_value(&register)),
+            Operand::Immediate(immediate) => Ok(immediate),
+            Operand::RegisterAddress(register) => self.load(&self.register_value(&register)),
+            Operand::ImmediateAddress(address) => self.load(&address),
+        }
+    }
+    /// writes to immediates are dropped
+    fn set_operand_value(&mut self, operand: Operand, value: Word) -> Result<(), VmError> {
+        match operand {
+            Operand::Register(register) => self.set_register_value(&register, value),
+            Operand::Immediate(_) => (),
+            Operand::RegisterAddress(register) => {
+                self.store(&self.register_value(&register), value)?
+            }
+            Operand::ImmediateAddress(address) => self.store(&address, value)?,
+        }
+        Ok(())
+    }
+    /// reads `destination`, then `source`, and writes what `action` makes of them to `destination`,
+    /// nothing is written when `action` faults
+    fn modify_operand<Action: FnOnce(Word, Word) -> Result<Word, VmError>>(
+        &mut self,
+        destination: Operand,
+        source: Operand,
+        action: Action,
+    ) -> Result<(), VmError> {
+        let address = match destination {
+            Operand::Register(register) => {
+                let value = self.register_value(&register);
+                let value = action(value, self.operand_value(source)?)?;
+                self.set_register_value(&register, value);
+                return Ok(());
+            }
+            Operand::Immediate(immediate) => {
+                action(immediate, self.operand_value(source)?)?;
+                return Ok(());
+            }
+            Operand::RegisterAddress(register) => self.register_value(&register),
+            Operand::ImmediateAddress(address) => address,
+        };
+        let value = self.load(&address)?;
+        let value = action(value, self.operand_value(source)?)?;
+        self.store(&address, value)
+    }
+}
+
+/// the flags `cmp` sets, equal, less and below
+fn compare(destination: Word, source: Word) -> Word {
+    [
+        (Flag::Equal, destination == source),
+        (Flag::Less, (destination as i32) < (source as i32)),
+        (Flag::Below, destination < source),
+    ]
+    .into_iter()
+    .filter(|(_, active)| *active)
+    .fold(0, |flags, (flag, _)| flags | flag.bit())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        test_util::assemble, ExecutionEngine, Profile, Register, StopReason, Vm, VmConfig,
+    };
+
+    #[test]
+    fn block_engine_falls_back_on_self_modifying_code() {
+        // 0x00: mov r0, 1
+        // 0x06: mov [0x02], 2 ; rewrites the immediate of the first instruction
+        // 0x10: jmp 0x00
+        let program = vec![
+            0x02, 0x10, 0x00, 0x00, 0x00, 0x01, //
+            0x02, 0xD0, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, //
+            0x11, 0x40, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x40));
+        vm.set_execution_engine(ExecutionEngine::BasicBlock);
+        assert_eq!(vm.run(3).0, StopReason::BudgetExhausted);
+        assert_eq!(vm.register_value(&Register::GeneralPurpose0), 1);
+        assert_eq!(vm.run(1).0, StopReason::BudgetExhausted);
+        assert_eq!(vm.register_value(&Register::GeneralPurpose0), 2);
+        assert_eq!(vm.run(3).0, StopReason::BudgetExhausted);
+        assert_eq!(vm.register_value(&Register::GeneralPurpose0), 2);
+    }
+
+    #[test]
+    fn block_engine_stops_at_breakpoints_inside_blocks() {
+        // mov r0, 1; mov r1, 2; hlt
+        let program = vec![
+            0x02, 0x10, 0x00, 0x00, 0x00, 0x01, //
+            0x02, 0x14, 0x00, 0x00, 0x00, 0x02, //
+            0x01,
+        ];
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x40));
+        vm.set_execution_engine(ExecutionEngine::BasicBlock);
+        vm.add_breakpoint(6);
+        assert_eq!(vm.run(10), (StopReason::Breakpoint(6), 1));
+        assert_eq!(vm.run(10), (StopReason::Halted, 2));
+        assert_eq!(vm.register_value(&Register::GeneralPurpose1), 2);
+    }
+
+    #[test]
+    fn block_engine_matches_the_interpreter() {
+        let program = assemble(
+            "main:
+            mov r0, 5
+            mov [0x100], 3
+            .loop:
+            add [0x100], r0
+            mul r1, [0x100]
+            xor r1, 0x55
+            not [0x100]
+            cmp r0, [0x100]
+            push r0
+            call double
+            pop [0x104]
+            sub r0, 1
+            jnz rel .loop, r0
+            div [0x104], 3
+            mov r1, fl
+            hlt
+            double:
+            shl [0x104], 1
+            ret",
+        );
+        let run = |engine| {
+            let config = VmConfig::new().memory_size(0x200).profile(Profile::Stack);
+            let mut vm = Vm::new(program.clone(), config);
+            vm.set_execution_engine(engine);
+            let (reason, executed) = vm.run(1000);
+            let registers = [
+                Register::GeneralPurpose0,
+                Register::GeneralPurpose1,
+                Register::Flag,
+                Register::ProgramCounter,
+            ]
+            .map(|register| vm.register_value(&register));
+            let memory = [0x100, 0x104, 0x1FC].map(|address| vm.memory_value(&address).unwrap());
+            (
+                reason,
+                executed,
+                registers,
+                memory,
+                vm.stack_pointer(),
+                vm.instruction_count(),
+                vm.cycle_count(),
+            )
+        };
+        let interpreted = run(ExecutionEngine::Interpreter);
+        assert_eq!(interpreted.0, StopReason::Halted);
+        assert_eq!(interpreted, run(ExecutionEngine::BasicBlock));
+    }
+}
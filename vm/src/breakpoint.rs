@@ -0,0 +1,84 @@
+use crate::{arch::Word, observer::VmObserver, vm::Vm};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub hit_count: u64,
+    /// temporary breakpoints are removed the first time they are hit
+    pub temporary: bool,
+}
+
+impl<O: VmObserver> Vm<O> {
+    pub fn add_breakpoint(&mut self, address: Word) {
+        self.breakpoints
+            .entry(address)
+            .or_insert(Breakpoint {
+                hit_count: 0,
+                temporary: false,
+            })
+            .temporary = false;
+    }
+    pub fn add_temporary_breakpoint(&mut self, address: Word) {
+        self.breakpoints.entry(address).or_insert(Breakpoint {
+            hit_count: 0,
+            temporary: true,
+        });
+    }
+    /// returns whether a breakpoint existed at `address`
+    pub fn remove_breakpoint(&mut self, address: Word) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+    pub fn breakpoint(&self, address: Word) -> Option<&Breakpoint> {
+        self.breakpoints.get(&address)
+    }
+    /// breakpoints sorted by address
+    pub fn breakpoints(&self) -> impl Iterator<Item = (Word, &Breakpoint)> {
+        self.breakpoints
+            .iter()
+            .map(|(address, breakpoint)| (*address, breakpoint))
+    }
+    /// registers a hit if a breakpoint exists at `address`, unless execution
+    /// is resuming from a breakpoint at that same address
+    pub(crate) fn hit_breakpoint(&mut self, address: Word) -> bool {
+        if self.resumed_breakpoint.take() == Some(address) {
+            return false;
+        }
+        let Some(breakpoint) = self.breakpoints.get_mut(&address) else {
+            return false;
+        };
+        breakpoint.hit_count += 1;
+        if breakpoint.temporary {
+            self.breakpoints.remove(&address);
+        }
+        self.resumed_breakpoint = Some(address);
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{StepOutcome, StopReason, Vm, VmConfig};
+
+    #[test]
+    fn breakpoint_stops_once_per_visit() {
+        // nop; nop; hlt
+        let mut vm = Vm::new(vec![0x00, 0x00, 0x01], VmConfig::new().memory_size(0x10));
+        vm.add_breakpoint(1);
+
+        assert_eq!(vm.run(10), (StopReason::Breakpoint(1), 1));
+        assert_eq!(vm.run(10), (StopReason::Halted, 2));
+        assert_eq!(vm.breakpoint(1).map(|v| v.hit_count), Some(1));
+    }
+
+    #[test]
+    fn temporary_breakpoint_is_removed() {
+        let mut vm = Vm::new(vec![0x00, 0x00, 0x01], VmConfig::new().memory_size(0x10));
+        vm.add_temporary_breakpoint(0);
+
+        assert_eq!(vm.run_next_instruction(), Ok(StepOutcome::Breakpoint(0)));
+        assert!(vm.breakpoint(0).is_none());
+        assert_eq!(vm.run_next_instruction(), Ok(StepOutcome::Executed));
+    }
+}
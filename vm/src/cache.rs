@@ -0,0 +1,121 @@
+use crate::{
+    arch::Word,
+    decode::MAX_INSTRUCTION_LENGTH,
+    error::VmError,
+    observer::VmObserver,
+    vm::{Instruction, Vm},
+};
+
+const PAGE_BITS: u32 = 12;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+
+type Page = [Option<(Instruction, u8)>; PAGE_SIZE];
+
+/// decoded instructions by address, split into lazily allocated pages so
+/// writes to memory without cached code are cheap to check
+pub(crate) struct InstructionCache {
+    enabled: bool,
+    pages: Vec<Option<Box<Page>>>,
+}
+
+impl InstructionCache {
+    pub(crate) fn new(memory_size: usize) -> Self {
+        let page_count = memory_size.div_ceil(PAGE_SIZE);
+        Self {
+            enabled: true,
+            pages: (0..page_count).map(|_| None).collect(),
+        }
+    }
+    fn page_index(address: Word) -> usize {
+        (address >> PAGE_BITS) as usize
+    }
+    fn entry_index(address: Word) -> usize {
+        address as usize & (PAGE_SIZE - 1)
+    }
+    pub(crate) fn get(&self, address: Word) -> Option<(Instruction, usize)> {
+        self.pages.get(Self::page_index(address))?.as_ref()?[Self::entry_index(address)]
+            .map(|(instruction, length)| (instruction, length.into()))
+    }
+    pub(crate) fn insert(&mut self, address: Word, instruction: Instruction, length: usize) {
+        if !self.enabled {
+            return;
+        }
+        let Some(page) = self.pages.get_mut(Self::page_index(address)) else {
+            return;
+        };
+        let page = page.get_or_insert_with(|| {
+            vec![None; PAGE_SIZE]
+                .into_boxed_slice()
+                .try_into()
+                .expect("vec has page size")
+        });
+        page[Self::entry_index(address)] = Some((
+            instruction,
+            length
+                .try_into()
+                .expect("instructions are at most 10 bytes"),
+        ));
+    }
+    /// drops every instruction which may overlap the word written at `address`
+    pub(crate) fn invalidate(&mut self, address: Word) {
+        let first = address.saturating_sub(MAX_INSTRUCTION_LENGTH as Word - 1);
+        let last = address.saturating_add(3);
+        for address in first..=last {
+            let Some(Some(page)) = self.pages.get_mut(Self::page_index(address)) else {
+                continue;
+            };
+            page[Self::entry_index(address)] = None;
+        }
+    }
+    /// drops every instruction, resizing the cache for `memory_size` bytes of memory
+    pub(crate) fn reset(&mut self, memory_size: usize) {
+        self.pages = (0..memory_size.div_ceil(PAGE_SIZE)).map(|_| None).collect();
+    }
+    pub(crate) fn clear(&mut self) {
+        self.pages.iter_mut().for_each(|page| *page = None);
+    }
+}
+
+impl<O: VmObserver> Vm<O> {
+    /// caching is enabled by default, disabling it drops every cached instruction
+    pub fn set_instruction_cache_enabled(&mut self, enabled: bool) {
+        self.instruction_cache.enabled = enabled;
+        if !enabled {
+            self.instruction_cache.clear();
+        }
+    }
+    /// decodes the instruction at `address`, reusing a previous decode if memory wasn't written to since
+    pub(crate) fn cached_decode_at(
+        &mut self,
+        address: Word,
+    ) -> Result<(Instruction, usize), VmError> {
+        if let Some(cached) = self.instruction_cache.get(address) {
+            return Ok(cached);
+        }
+        let (instruction, length) = self.decode_at(address)?;
+        self.instruction_cache.insert(address, instruction, length);
+        Ok((instruction, length))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Register, StopReason, Vm, VmConfig};
+
+    #[test]
+    fn self_modifying_code_invalidates_cache() {
+        // 0x00: mov r0, 1
+        // 0x06: mov [0x02], 2 ; rewrites the immediate of the first instruction
+        // 0x10: jmp 0x00
+        let program = vec![
+            0x02, 0x10, 0x00, 0x00, 0x00, 0x01, //
+            0x02, 0xD0, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, //
+            0x11, 0x40, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x40));
+        assert_eq!(vm.run(3).0, StopReason::BudgetExhausted);
+        assert_eq!(vm.register_value(&Register::GeneralPurpose0), 1);
+        assert_eq!(vm.run(1).0, StopReason::BudgetExhausted);
+        assert_eq!(vm.register_value(&Register::GeneralPurpose0), 2);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{arch::Word, memory::MemoryBackend, vm::Instruction};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ShiftMode {
+    /// bits shifted out are shifted back in on the other side
+    #[default]
+    Rotate,
+    /// bits shifted out are dropped and zeroes are shifted in
+    Logical,
+}
+
+/// which instructions the vm runs besides the original ones
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// the opcodes of later extensions are invalid, like in previous versions
+    #[default]
+    Base,
+    /// adds a stack pointer starting at the end of memory, along with `push`, `pop`, `call` and `ret`
+    Stack,
+}
+
+impl Profile {
+    pub(crate) fn supports(self, instruction: &Instruction) -> bool {
+        match instruction {
+            Instruction::Push(_)
+            | Instruction::Pop(_)
+            | Instruction::Call(_)
+            | Instruction::Ret => self == Profile::Stack,
+            _ => true,
+        }
+    }
+}
+
+/// byte order of words read from and written to memory,
+/// immediates in instructions are always big endian
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    #[default]
+    Big,
+    Little,
+}
+
+impl Endianness {
+    pub(crate) fn word_to_bytes(self, word: Word) -> [u8; 4] {
+        match self {
+            Endianness::Big => word.to_be_bytes(),
+            Endianness::Little => word.to_le_bytes(),
+        }
+    }
+    pub(crate) fn bytes_to_word(self, bytes: [u8; 4]) -> Word {
+        match self {
+            Endianness::Big => Word::from_be_bytes(bytes),
+            Endianness::Little => Word::from_le_bytes(bytes),
+        }
+    }
+}
+
+/// memory of a default [`VmConfig`], the inspector's default memory size
+pub const DEFAULT_MEMORY_SIZE: usize = 0x30000;
+
+/// how a [`Vm`](crate::Vm) is built and which semantics its instructions follow,
+/// the default matches the behavior of previous versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VmConfig {
+    /// bytes of memory, the instructions are loaded at the start of it
+    pub memory_size: usize,
+    pub memory_backend: MemoryBackend,
+    /// used by `shl` and `shr`
+    pub shift: ShiftMode,
+    /// whether `add` and `sub` add the carry/borrow flag in, like `adc` and `sbb`
+    pub carry_in: bool,
+    pub endianness: Endianness,
+    pub profile: Profile,
+}
+
+impl Default for VmConfig {
+    fn default() -> Self {
+        Self {
+            memory_size: DEFAULT_MEMORY_SIZE,
+            memory_backend: MemoryBackend::Flat,
+            shift: ShiftMode::Rotate,
+            carry_in: true,
+            endianness: Endianness::Big,
+            profile: Profile::Base,
+        }
+    }
+}
+
+impl VmConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn memory_size(mut self, memory_size: usize) -> Self {
+        self.memory_size = memory_size;
+        self
+    }
+    pub fn memory_backend(mut self, memory_backend: MemoryBackend) -> Self {
+        self.memory_backend = memory_backend;
+        self
+    }
+    pub fn shift(mut self, shift: ShiftMode) -> Self {
+        self.shift = shift;
+        self
+    }
+    pub fn carry_in(mut self, carry_in: bool) -> Self {
+        self.carry_in = carry_in;
+        self
+    }
+    pub fn endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+}
@@ -0,0 +1,177 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{arch::Word, observer::VmObserver, vm::Instruction};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BranchCoverage {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+impl BranchCoverage {
+    /// whether the jump went both ways
+    pub fn is_covered(&self) -> bool {
+        self.taken > 0 && self.not_taken > 0
+    }
+}
+
+/// records which instructions ran and which way conditional jumps went,
+/// attach it with [`Vm::with_observer`](crate::Vm::with_observer)
+#[derive(Debug, Default, Clone)]
+pub struct Coverage {
+    /// executions of every instruction start address
+    instructions: BTreeMap<Word, u64>,
+    branches: BTreeMap<Word, BranchCoverage>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// executed instruction addresses in order with how often they ran
+    pub fn instructions(&self) -> impl Iterator<Item = (Word, u64)> + '_ {
+        self.instructions
+            .iter()
+            .map(|(address, executions)| (*address, *executions))
+    }
+    /// executed `jz` and `jnz` addresses in order
+    pub fn branches(&self) -> impl Iterator<Item = (Word, &BranchCoverage)> {
+        self.branches
+            .iter()
+            .map(|(address, branch)| (*address, branch))
+    }
+    pub fn is_executed(&self, address: Word) -> bool {
+        self.instructions.contains_key(&address)
+    }
+    /// one line per address, ordered by address:
+    /// `instruction <address> <executions>` followed by `branch <address> <taken> <not taken>`
+    pub fn export(&self) -> String {
+        let mut out = String::new();
+        for (address, executions) in self.instructions() {
+            writeln!(out, "instruction {address:#010X} {executions}").expect("writing to a string");
+        }
+        for (address, branch) in self.branches() {
+            let BranchCoverage { taken, not_taken } = branch;
+            writeln!(out, "branch {address:#010X} {taken} {not_taken}")
+                .expect("writing to a string");
+        }
+        out
+    }
+    /// an lcov tracefile for `source_file`, `lines` maps every instruction address to its line,
+    /// e.g. as written by `vc2-assembler --lines`
+    ///
+    /// every conditional jump which ran is reported as a block of two branches, taken and not taken,
+    /// the block number is the address of the jump
+    pub fn lcov(
+        &self,
+        source_file: &str,
+        lines: impl IntoIterator<Item = (Word, usize)>,
+    ) -> String {
+        let mut line_hits = BTreeMap::<usize, u64>::new();
+        let mut branches = Vec::new();
+        for (address, line) in lines {
+            let executions = self.instructions.get(&address).copied().unwrap_or(0);
+            *line_hits.entry(line).or_default() += executions;
+            if let Some(branch) = self.branches.get(&address) {
+                branches.push((line, address, *branch));
+            }
+        }
+        branches.sort_by_key(|(line, address, _)| (*line, *address));
+
+        let mut out = String::new();
+        writeln!(out, "TN:\nSF:{source_file}").expect("writing to a string");
+        for (line, address, branch) in &branches {
+            for (index, count) in [branch.taken, branch.not_taken].into_iter().enumerate() {
+                writeln!(out, "BRDA:{line},{address},{index},{count}")
+                    .expect("writing to a string");
+            }
+        }
+        for (line, hits) in &line_hits {
+            writeln!(out, "DA:{line},{hits}").expect("writing to a string");
+        }
+        let lines_hit = line_hits.values().filter(|hits| **hits > 0).count();
+        writeln!(out, "LF:{}\nLH:{lines_hit}", line_hits.len()).expect("writing to a string");
+        let branches_hit: usize = branches
+            .iter()
+            .map(|(_, _, branch)| usize::from(branch.taken > 0) + usize::from(branch.not_taken > 0))
+            .sum();
+        writeln!(
+            out,
+            "BRF:{}\nBRH:{branches_hit}\nend_of_record",
+            branches.len() * 2
+        )
+        .expect("writing to a string");
+        out
+    }
+}
+
+impl VmObserver for Coverage {
+    fn on_instruction(&mut self, pc: Word, _instruction: &Instruction) {
+        *self.instructions.entry(pc).or_default() += 1;
+    }
+    fn on_branch(&mut self, pc: Word, taken: bool) {
+        let branch = self.branches.entry(pc).or_default();
+        if taken {
+            branch.taken += 1;
+        } else {
+            branch.not_taken += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BranchCoverage, Coverage};
+    use crate::{test_util::assemble, StopReason, Vm, VmConfig};
+
+    #[test]
+    fn records_executed_instructions_and_branch_directions() {
+        // `jnz` is at 0x0C and `jz` at 0x12, the first `hlt` at 0x18 is never reached
+        let program = assemble(
+            "mov r0, 2\n\
+             loop:\n\
+             sub r0, 1\n\
+             jnz loop, r0\n\
+             jz done, r0\n\
+             hlt\n\
+             done:\n\
+             hlt",
+        );
+        let mut vm =
+            Vm::new(program, VmConfig::new().memory_size(0x40)).with_observer(Coverage::new());
+        assert_eq!(vm.run(100).0, StopReason::Halted);
+
+        let coverage = vm.observer();
+        let jnz = BranchCoverage {
+            taken: 1,
+            not_taken: 1,
+        };
+        let jz = BranchCoverage {
+            taken: 1,
+            not_taken: 0,
+        };
+        assert_eq!(
+            coverage.branches().collect::<Vec<_>>(),
+            vec![(0x0C, &jnz), (0x12, &jz)]
+        );
+        assert!(jnz.is_covered() && !jz.is_covered());
+        assert!(!coverage.is_executed(0x18));
+        assert!(coverage
+            .export()
+            .ends_with("instruction 0x00000019 1\nbranch 0x0000000C 1 1\nbranch 0x00000012 1 0\n"));
+
+        let lines = [
+            (0x00, 1),
+            (0x06, 3),
+            (0x0C, 4),
+            (0x12, 5),
+            (0x18, 6),
+            (0x19, 8),
+        ];
+        let lcov = coverage.lcov("loop.asm", lines);
+        assert!(lcov.starts_with("TN:\nSF:loop.asm\nBRDA:4,12,0,1\nBRDA:4,12,1,1\n"));
+        assert!(lcov.contains("BRDA:5,18,1,0\nDA:1,1\n"));
+        assert!(lcov.contains("DA:3,2\n"));
+        assert!(lcov.ends_with("DA:6,0\nDA:8,1\nLF:6\nLH:5\nBRF:4\nBRH:3\nend_of_record\n"));
+    }
+}
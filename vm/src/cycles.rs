@@ -0,0 +1,162 @@
+use crate::vm::{
+    CallConfig, ConditionalJmpConfig, Config, Instruction, JmpConfig, NotConfig, PopConfig,
+    PushConfig,
+};
+
+/// cycles added for every operand in memory and every stack access
+const MEMORY_ACCESS_CYCLES: u64 = 2;
+
+impl Instruction {
+    /// cycles the instruction takes, counted by [`Vm::cycle_count`](crate::Vm::cycle_count)
+    ///
+    /// a base cost of 1, 3 for multiplication, 8 for division and 2 for control flow,
+    /// plus 2 for every memory operand or stack access,
+    /// operands which are both read and written count once
+    pub fn cycles(&self) -> u64 {
+        let (base, memory_accesses) = match self {
+            Instruction::Nop | Instruction::Hlt => (1, 0),
+            Instruction::Mov(config)
+            | Instruction::Or(config)
+            | Instruction::And(config)
+            | Instruction::Xor(config)
+            | Instruction::Shl(config)
+            | Instruction::Shr(config)
+            | Instruction::Add(config)
+            | Instruction::Sub(config)
+            | Instruction::Cmp(config) => (1, config.memory_operands()),
+            Instruction::Mul(config) | Instruction::IMul(config) => (3, config.memory_operands()),
+            Instruction::Div(config) | Instruction::IDiv(config) | Instruction::Rem(config) => {
+                (8, config.memory_operands())
+            }
+            Instruction::Not(config) => (1, config.memory_operands()),
+            Instruction::Jmp(config, _) => (2, config.memory_operands()),
+            Instruction::Jz(config, _) | Instruction::Jnz(config, _) => {
+                (2, config.memory_operands())
+            }
+            Instruction::Push(config) => (1, config.memory_operands() + 1),
+            Instruction::Pop(config) => (1, config.memory_operands() + 1),
+            Instruction::Call(config) => (2, config.memory_operands() + 1),
+            Instruction::Ret => (2, 1),
+        };
+        base + memory_accesses * MEMORY_ACCESS_CYCLES
+    }
+}
+
+impl Config {
+    fn memory_operands(&self) -> u64 {
+        match self {
+            Config::RegisterFromRegister(..)
+            | Config::RegisterFromImmediate(..)
+            | Config::ImmediateFromImmediate(..)
+            | Config::ImmediateFromRegister(..) => 0,
+            Config::RegisterFromRegisterAddress(..)
+            | Config::RegisterFromImmediateAddress(..)
+            | Config::RegisterAddressFromRegister(..)
+            | Config::RegisterAddressFromImmediate(..)
+            | Config::ImmediateAddressFromRegister(..)
+            | Config::ImmediateAddressFromImmediate(..) => 1,
+        }
+    }
+}
+
+impl ConditionalJmpConfig {
+    fn memory_operands(&self) -> u64 {
+        match self {
+            ConditionalJmpConfig::RegisterFromRegister(..)
+            | ConditionalJmpConfig::RegisterFromImmediate(..)
+            | ConditionalJmpConfig::ImmediateFromRegister(..)
+            | ConditionalJmpConfig::ImmediateFromImmediate(..) => 0,
+            ConditionalJmpConfig::RegisterFromRegisterAddress(..)
+            | ConditionalJmpConfig::RegisterFromImmediateAddress(..)
+            | ConditionalJmpConfig::ImmediateFromRegisterAddress(..)
+            | ConditionalJmpConfig::ImmediateFromImmediateAddress(..)
+            | ConditionalJmpConfig::RegisterAddressFromRegister(..)
+            | ConditionalJmpConfig::RegisterAddressFromImmediate(..)
+            | ConditionalJmpConfig::ImmediateAddressFromRegister(..)
+            | ConditionalJmpConfig::ImmediateAddressFromImmediate(..) => 1,
+        }
+    }
+}
+
+impl JmpConfig {
+    fn memory_operands(&self) -> u64 {
+        match self {
+            JmpConfig::Register(_) | JmpConfig::Immediate(_) => 0,
+            JmpConfig::RegisterAddress(_) | JmpConfig::ImmediateAddress(_) => 1,
+        }
+    }
+}
+
+impl NotConfig {
+    fn memory_operands(&self) -> u64 {
+        match self {
+            NotConfig::Register(_) => 0,
+            NotConfig::RegisterAddress(_) | NotConfig::ImmediateAddress(_) => 1,
+        }
+    }
+}
+
+impl PushConfig {
+    fn memory_operands(&self) -> u64 {
+        match self {
+            PushConfig::Register(_) | PushConfig::Immediate(_) => 0,
+            PushConfig::RegisterAddress(_) | PushConfig::ImmediateAddress(_) => 1,
+        }
+    }
+}
+
+impl PopConfig {
+    fn memory_operands(&self) -> u64 {
+        match self {
+            PopConfig::Register(_) => 0,
+            PopConfig::RegisterAddress(_) | PopConfig::ImmediateAddress(_) => 1,
+        }
+    }
+}
+
+impl CallConfig {
+    fn memory_operands(&self) -> u64 {
+        match self {
+            CallConfig::Register(_) | CallConfig::Immediate(_) => 0,
+            CallConfig::RegisterAddress(_) | CallConfig::ImmediateAddress(_) => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{decode, test_util::assemble, Register, StopReason, Vm, VmConfig};
+
+    #[test]
+    fn memory_operands_cost_more() {
+        let cases = [
+            ("mov r0, 1", 1),
+            ("mov r0, [0x10]", 3),
+            ("add [r1], 2", 3),
+            ("div r0, r1", 8),
+            ("jz 0x10, [r0]", 4),
+            ("jmp [0x10]", 4),
+            ("call 0x10", 4),
+            ("pop [r0]", 5),
+        ];
+        for (source, cycles) in cases {
+            let (instruction, _) = decode(&assemble(source), 0).unwrap();
+            assert_eq!(instruction.cycles(), cycles, "{source}");
+        }
+    }
+
+    #[test]
+    fn counters_follow_execution_and_undo() {
+        let program = assemble("mov r0, 3\nmov [0x40], r0\nmul r0, r0\nhlt");
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x80));
+        vm.set_history_capacity(4);
+        assert_eq!(vm.run(10).0, StopReason::Halted);
+        assert_eq!(vm.register_value(&Register::GeneralPurpose0), 9);
+        assert_eq!(
+            (vm.instruction_count(), vm.cycle_count()),
+            (4, 1 + 3 + 3 + 1)
+        );
+        assert_eq!(vm.step_back(2), 2);
+        assert_eq!((vm.instruction_count(), vm.cycle_count()), (2, 1 + 3));
+    }
+}
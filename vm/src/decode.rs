@@ -0,0 +1,482 @@
+use std::fmt::Display;
+
+use crate::{
+    arch::Word,
+    error::VmError,
+    named_instruction::{self, NamedInstruction},
+    vm::{
+        CallConfig, ConditionalJmpConfig, Config, Immediate, Instruction, JmpConfig, JmpVariant,
+        NotConfig, PopConfig, PushConfig, Register, Selector,
+    },
+};
+
+/// opcode, selectors and two immediates
+pub const MAX_INSTRUCTION_LENGTH: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    InvalidOpcode {
+        address: Word,
+        opcode: u8,
+    },
+    InvalidSelectorCombo {
+        address: Word,
+        opcode: u8,
+        selectors: u8,
+    },
+    /// the instruction continues past the end of the given bytes
+    UnexpectedEnd {
+        address: Word,
+        len: usize,
+    },
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::InvalidOpcode { address, opcode } => {
+                write!(f, "unrecognized instruction '{opcode:#04X}' at {address:#010X}")
+            }
+            DecodeError::InvalidSelectorCombo {
+                address,
+                opcode,
+                selectors,
+            } => write!(
+                f,
+                "invalid selector/destination combo '{selectors:#010b}' for instruction '{opcode:#04X}' at {address:#010X}"
+            ),
+            DecodeError::UnexpectedEnd { address, len } => {
+                write!(f, "instruction at {address:#010X} is cut off after {len} bytes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<DecodeError> for VmError {
+    fn from(value: DecodeError) -> Self {
+        match value {
+            DecodeError::InvalidOpcode { address, opcode } => VmError::InvalidOpcode {
+                pc: address,
+                opcode,
+            },
+            DecodeError::InvalidSelectorCombo {
+                address,
+                opcode,
+                selectors,
+            } => VmError::InvalidSelectorCombo {
+                pc: address,
+                opcode,
+                selectors,
+            },
+            DecodeError::UnexpectedEnd { address, len } => {
+                VmError::TruncatedInstruction { pc: address, len }
+            }
+        }
+    }
+}
+
+/// decodes the instruction at the start of `bytes`, returning it along with its length
+///
+/// `address` is the location of `bytes[0]`, used for error reporting
+pub fn decode(bytes: &[u8], address: Word) -> Result<(Instruction, usize), DecodeError> {
+    let mut decoder = Decoder {
+        bytes,
+        address,
+        cursor: 0,
+    };
+    let instruction = decoder.parse_instruction()?;
+    Ok((instruction, decoder.cursor))
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    address: Word,
+    cursor: usize,
+}
+
+struct Operands {
+    destination_selector: Selector,
+    source_selector: Selector,
+    destination: Register,
+    source: Register,
+}
+
+impl<'a> Decoder<'a> {
+    fn current_byte(&self) -> Result<u8, DecodeError> {
+        self.bytes
+            .get(self.cursor)
+            .copied()
+            .ok_or(DecodeError::UnexpectedEnd {
+                address: self.address,
+                len: self.cursor,
+            })
+    }
+    fn step(&mut self) {
+        self.cursor += 1;
+    }
+    fn consume_byte(&mut self) -> Result<u8, DecodeError> {
+        let byte = self.current_byte()?;
+        self.step();
+        Ok(byte)
+    }
+    fn consume_immediate(&mut self) -> Result<Immediate, DecodeError> {
+        let byte_0 = self.consume_byte()?;
+        let byte_1 = self.consume_byte()?;
+        let byte_2 = self.consume_byte()?;
+        let byte_3 = self.consume_byte()?;
+        Ok(u32::from_be_bytes([byte_0, byte_1, byte_2, byte_3]))
+    }
+    fn invalid_selector_combo(&self) -> DecodeError {
+        DecodeError::InvalidSelectorCombo {
+            address: self.address,
+            opcode: self.bytes[0],
+            selectors: self.bytes.get(1).copied().unwrap_or_default(),
+        }
+    }
+    fn consume_operands(&mut self) -> Result<Operands, DecodeError> {
+        let input = self.consume_byte()?;
+        log::debug!("parsing operands ({input:#010b})");
+
+        let selector = |value: u8| Selector::try_from(value).expect("selector is 2 bits");
+        let register = |value: u8| Register::try_from(value).expect("register is 2 bits");
+
+        Ok(Operands {
+            destination_selector: selector((input & 0b1100_0000) >> 6),
+            source_selector: selector((input & 0b0011_0000) >> 4),
+            destination: register((input & 0b0000_1100) >> 2),
+            source: register(input & 0b0000_0011),
+        })
+    }
+    fn parse_target(&mut self) -> Result<Config, DecodeError> {
+        let Operands {
+            destination_selector,
+            source_selector,
+            destination,
+            source,
+        } = self.consume_operands()?;
+        let config = match (destination_selector, source_selector) {
+            (Selector::Register, Selector::Register) => {
+                Config::RegisterFromRegister(destination, source)
+            }
+            (Selector::Register, Selector::Immediate) => {
+                Config::RegisterFromImmediate(destination, self.consume_immediate()?)
+            }
+            (Selector::Register, Selector::RegisterAddress) => {
+                Config::RegisterFromRegisterAddress(destination, source)
+            }
+            (Selector::Register, Selector::ImmediateAddress) => {
+                Config::RegisterFromImmediateAddress(destination, self.consume_immediate()?)
+            }
+            (Selector::RegisterAddress, Selector::Register) => {
+                Config::RegisterAddressFromRegister(destination, source)
+            }
+            (Selector::RegisterAddress, Selector::Immediate) => {
+                Config::RegisterAddressFromImmediate(destination, self.consume_immediate()?)
+            }
+            (Selector::ImmediateAddress, Selector::Register) => {
+                Config::ImmediateAddressFromRegister(self.consume_immediate()?, source)
+            }
+            (Selector::ImmediateAddress, Selector::Immediate) => {
+                Config::ImmediateAddressFromImmediate(
+                    self.consume_immediate()?,
+                    self.consume_immediate()?,
+                )
+            }
+            (Selector::Immediate, Selector::Immediate) => {
+                Config::ImmediateFromImmediate(self.consume_immediate()?, self.consume_immediate()?)
+            }
+            (Selector::Immediate, Selector::Register) => {
+                Config::ImmediateFromRegister(self.consume_immediate()?, source)
+            }
+            _ => return Err(self.invalid_selector_combo()),
+        };
+
+        Ok(config)
+    }
+    fn parse_conditional_jmp_target(&mut self) -> Result<ConditionalJmpConfig, DecodeError> {
+        let Operands {
+            destination_selector,
+            source_selector,
+            destination,
+            source,
+        } = self.consume_operands()?;
+        let config = match (destination_selector, source_selector) {
+            (Selector::Register, Selector::Register) => {
+                ConditionalJmpConfig::RegisterFromRegister(destination, source)
+            }
+            (Selector::Register, Selector::Immediate) => {
+                ConditionalJmpConfig::RegisterFromImmediate(destination, self.consume_immediate()?)
+            }
+            (Selector::Register, Selector::RegisterAddress) => {
+                ConditionalJmpConfig::RegisterFromRegisterAddress(destination, source)
+            }
+            (Selector::Register, Selector::ImmediateAddress) => {
+                ConditionalJmpConfig::RegisterFromImmediateAddress(
+                    destination,
+                    self.consume_immediate()?,
+                )
+            }
+            (Selector::RegisterAddress, Selector::Register) => {
+                ConditionalJmpConfig::RegisterAddressFromRegister(destination, source)
+            }
+            (Selector::RegisterAddress, Selector::Immediate) => {
+                ConditionalJmpConfig::RegisterAddressFromImmediate(
+                    destination,
+                    self.consume_immediate()?,
+                )
+            }
+            (Selector::ImmediateAddress, Selector::Register) => {
+                ConditionalJmpConfig::ImmediateAddressFromRegister(
+                    self.consume_immediate()?,
+                    source,
+                )
+            }
+            (Selector::ImmediateAddress, Selector::Immediate) => {
+                ConditionalJmpConfig::ImmediateAddressFromImmediate(
+                    self.consume_immediate()?,
+                    self.consume_immediate()?,
+                )
+            }
+            (Selector::Immediate, Selector::Immediate) => {
+                ConditionalJmpConfig::ImmediateFromImmediate(
+                    self.consume_immediate()?,
+                    self.consume_immediate()?,
+                )
+            }
+            (Selector::Immediate, Selector::Register) => {
+                ConditionalJmpConfig::ImmediateFromRegister(self.consume_immediate()?, source)
+            }
+            (Selector::Immediate, Selector::RegisterAddress) => {
+                ConditionalJmpConfig::ImmediateFromRegisterAddress(
+                    self.consume_immediate()?,
+                    source,
+                )
+            }
+            (Selector::Immediate, Selector::ImmediateAddress) => {
+                ConditionalJmpConfig::ImmediateFromImmediateAddress(
+                    self.consume_immediate()?,
+                    self.consume_immediate()?,
+                )
+            }
+            _ => return Err(self.invalid_selector_combo()),
+        };
+
+        Ok(config)
+    }
+    fn parse_not_target(&mut self) -> Result<NotConfig, DecodeError> {
+        let Operands {
+            destination_selector,
+            destination,
+            ..
+        } = self.consume_operands()?;
+
+        let config = match destination_selector {
+            Selector::Register => NotConfig::Register(destination),
+            Selector::Immediate => return Err(self.invalid_selector_combo()),
+            Selector::RegisterAddress => NotConfig::RegisterAddress(destination),
+            Selector::ImmediateAddress => NotConfig::ImmediateAddress(self.consume_immediate()?),
+        };
+
+        Ok(config)
+    }
+    fn parse_jmp_target(&mut self) -> Result<JmpConfig, DecodeError> {
+        let Operands {
+            destination_selector,
+            destination,
+            ..
+        } = self.consume_operands()?;
+
+        let config = match destination_selector {
+            Selector::Register => JmpConfig::Register(destination),
+            Selector::Immediate => JmpConfig::Immediate(self.consume_immediate()?),
+            Selector::RegisterAddress => JmpConfig::RegisterAddress(destination),
+            Selector::ImmediateAddress => JmpConfig::ImmediateAddress(self.consume_immediate()?),
+        };
+
+        Ok(config)
+    }
+    fn parse_push_source(&mut self) -> Result<PushConfig, DecodeError> {
+        let Operands {
+            destination_selector,
+            destination,
+            ..
+        } = self.consume_operands()?;
+
+        let config = match destination_selector {
+            Selector::Register => PushConfig::Register(destination),
+            Selector::Immediate => PushConfig::Immediate(self.consume_immediate()?),
+            Selector::RegisterAddress => PushConfig::RegisterAddress(destination),
+            Selector::ImmediateAddress => PushConfig::ImmediateAddress(self.consume_immediate()?),
+        };
+
+        Ok(config)
+    }
+    fn parse_pop_target(&mut self) -> Result<PopConfig, DecodeError> {
+        let Operands {
+            destination_selector,
+            destination,
+            ..
+        } = self.consume_operands()?;
+
+        let config = match destination_selector {
+            Selector::Register => PopConfig::Register(destination),
+            Selector::Immediate => return Err(self.invalid_selector_combo()),
+            Selector::RegisterAddress => PopConfig::RegisterAddress(destination),
+            Selector::ImmediateAddress => PopConfig::ImmediateAddress(self.consume_immediate()?),
+        };
+
+        Ok(config)
+    }
+    fn parse_call_target(&mut self) -> Result<CallConfig, DecodeError> {
+        let Operands {
+            destination_selector,
+            destination,
+            ..
+        } = self.consume_operands()?;
+
+        let config = match destination_selector {
+            Selector::Register => CallConfig::Register(destination),
+            Selector::Immediate => CallConfig::Immediate(self.consume_immediate()?),
+            Selector::RegisterAddress => CallConfig::RegisterAddress(destination),
+            Selector::ImmediateAddress => CallConfig::ImmediateAddress(self.consume_immediate()?),
+        };
+
+        Ok(config)
+    }
+    fn parse_instruction(&mut self) -> Result<Instruction, DecodeError> {
+        let opcode = self.consume_byte()?;
+        let instruction =
+            NamedInstruction::try_from(opcode).map_err(|opcode| DecodeError::InvalidOpcode {
+                address: self.address,
+                opcode,
+            })?;
+        log::debug!("parsing instruction {instruction:?} ({opcode:#02X})");
+        let instruction = match instruction {
+            named_instruction::Nop => Instruction::Nop,
+            named_instruction::Hlt => Instruction::Hlt,
+            named_instruction::Mov => Instruction::Mov(self.parse_target()?),
+            named_instruction::Or => Instruction::Or(self.parse_target()?),
+            named_instruction::And => Instruction::And(self.parse_target()?),
+            named_instruction::Xor => Instruction::Xor(self.parse_target()?),
+            named_instruction::Shl => Instruction::Shl(self.parse_target()?),
+            named_instruction::Shr => Instruction::Shr(self.parse_target()?),
+            named_instruction::Add => Instruction::Add(self.parse_target()?),
+            named_instruction::Sub => Instruction::Sub(self.parse_target()?),
+            named_instruction::Mul => Instruction::Mul(self.parse_target()?),
+            named_instruction::IMul => Instruction::IMul(self.parse_target()?),
+            named_instruction::Div => Instruction::Div(self.parse_target()?),
+            named_instruction::IDiv => Instruction::IDiv(self.parse_target()?),
+            named_instruction::Rem => Instruction::Rem(self.parse_target()?),
+            named_instruction::Cmp => Instruction::Cmp(self.parse_target()?),
+            named_instruction::Not => Instruction::Not(self.parse_not_target()?),
+            named_instruction::Jmp => {
+                Instruction::Jmp(self.parse_jmp_target()?, JmpVariant::Absolute)
+            }
+            named_instruction::Jz => {
+                Instruction::Jz(self.parse_conditional_jmp_target()?, JmpVariant::Absolute)
+            }
+            named_instruction::Jnz => {
+                Instruction::Jnz(self.parse_conditional_jmp_target()?, JmpVariant::Absolute)
+            }
+            named_instruction::JmpRel => {
+                Instruction::Jmp(self.parse_jmp_target()?, JmpVariant::Relative)
+            }
+            named_instruction::JzRel => {
+                Instruction::Jz(self.parse_conditional_jmp_target()?, JmpVariant::Relative)
+            }
+            named_instruction::JnzRel => {
+                Instruction::Jnz(self.parse_conditional_jmp_target()?, JmpVariant::Relative)
+            }
+            named_instruction::Push => Instruction::Push(self.parse_push_source()?),
+            named_instruction::Pop => Instruction::Pop(self.parse_pop_target()?),
+            named_instruction::Call => Instruction::Call(self.parse_call_target()?),
+            named_instruction::Ret => Instruction::Ret,
+        };
+        Ok(instruction)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decode, ConditionalJmpConfig, Config, DecodeError, Instruction, JmpConfig, JmpVariant,
+        Register, Vm, VmConfig,
+    };
+
+    #[test]
+    fn decode_reports_length() {
+        // mov r0, [0x1000]
+        let bytes = [0x02, 0x30, 0x00, 0x00, 0x10, 0x00, 0xFF];
+        assert_eq!(
+            decode(&bytes, 0),
+            Ok((
+                Instruction::Mov(Config::RegisterFromImmediateAddress(
+                    Register::GeneralPurpose0,
+                    0x1000
+                )),
+                6
+            ))
+        );
+        assert_eq!(decode(&[0x01], 0), Ok((Instruction::Hlt, 1)));
+    }
+
+    #[test]
+    fn decode_relative_jumps() {
+        // jmp rel -8
+        let bytes = [0x14, 0x40, 0xFF, 0xFF, 0xFF, 0xF8];
+        let jmp = Instruction::Jmp(JmpConfig::Immediate(-8i32 as u32), JmpVariant::Relative);
+        assert_eq!(decode(&bytes, 0), Ok((jmp, 6)));
+        // jnz rel r1, r0
+        let jnz = Instruction::Jnz(
+            ConditionalJmpConfig::RegisterFromRegister(
+                Register::GeneralPurpose1,
+                Register::GeneralPurpose0,
+            ),
+            JmpVariant::Relative,
+        );
+        assert_eq!(decode(&[0x16, 0x04], 0), Ok((jnz, 2)));
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(
+            decode(&[0xFF], 4),
+            Err(DecodeError::InvalidOpcode {
+                address: 4,
+                opcode: 0xFF
+            })
+        );
+        // not <immediate>
+        assert_eq!(
+            decode(&[0x06, 0x40], 0),
+            Err(DecodeError::InvalidSelectorCombo {
+                address: 0,
+                opcode: 0x06,
+                selectors: 0x40
+            })
+        );
+        assert_eq!(
+            decode(&[0x11, 0x40, 0x00], 0),
+            Err(DecodeError::UnexpectedEnd { address: 0, len: 3 })
+        );
+    }
+
+    #[test]
+    fn decode_at_does_not_execute() {
+        // jmp 0x10
+        let vm = Vm::new(
+            vec![0x11, 0x40, 0x00, 0x00, 0x00, 0x10],
+            VmConfig::new().memory_size(0x20),
+        );
+        assert_eq!(
+            vm.decode_at(0),
+            Ok((
+                Instruction::Jmp(JmpConfig::Immediate(0x10), JmpVariant::Absolute),
+                6
+            ))
+        );
+        assert_eq!(vm.register_value(&Register::ProgramCounter), 0);
+    }
+}
@@ -0,0 +1,147 @@
+use std::ops::Range;
+
+use crate::{arch::Word, observer::VmObserver, snapshot::SnapshotError, vm::Vm};
+
+/// a peripheral mapped into memory, accessed a word at a time
+///
+/// offsets are relative to the start of the range the device is mapped at
+pub trait Device: Send {
+    /// reads a word without side effects, used when inspecting memory
+    fn peek(&self, offset: Word) -> Word;
+    /// reads a word on behalf of the running program
+    fn read(&mut self, offset: Word) -> Word {
+        self.peek(offset)
+    }
+    fn write(&mut self, offset: Word, value: Word);
+    /// called once for every executed instruction, before it runs
+    fn tick(&mut self) {}
+    /// state which can't be read back through [`Device::peek`], kept in snapshots
+    fn save_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+    /// replaces the state with one returned by [`Device::save_state`]
+    fn restore_state(&mut self, _state: &[u8]) -> Result<(), SnapshotError> {
+        Ok(())
+    }
+}
+
+pub(crate) struct MappedDevice {
+    pub(crate) range: Range<Word>,
+    pub(crate) device: Box<dyn Device>,
+}
+
+impl<O: VmObserver> Vm<O> {
+    /// routes word accesses starting in `range` to `device` instead of ram,
+    /// returns an id used to unmap the device
+    ///
+    /// devices mapped later take precedence where ranges overlap,
+    /// instructions are always fetched from ram
+    pub fn map_device(&mut self, range: Range<Word>, device: Box<dyn Device>) -> usize {
+        let id = self.next_device_id;
+        self.next_device_id += 1;
+        self.devices.insert(id, MappedDevice { range, device });
+        id
+    }
+    /// returns the device mapped with `id` if there was one
+    pub fn unmap_device(&mut self, id: usize) -> Option<Box<dyn Device>> {
+        self.devices.remove(&id).map(|mapped| mapped.device)
+    }
+    /// mapped address ranges sorted by device id
+    pub fn devices(&self) -> impl Iterator<Item = (usize, &Range<Word>)> {
+        self.devices.iter().map(|(id, mapped)| (*id, &mapped.range))
+    }
+    pub(crate) fn device_at(&self, address: Word) -> Option<(&dyn Device, Word)> {
+        self.devices
+            .values()
+            .rev()
+            .find(|mapped| mapped.range.contains(&address))
+            .map(|mapped| (mapped.device.as_ref(), address - mapped.range.start))
+    }
+    pub(crate) fn device_at_mut(
+        &mut self,
+        address: Word,
+    ) -> Option<(&mut (dyn Device + 'static), Word)> {
+        self.devices
+            .values_mut()
+            .rev()
+            .find(|mapped| mapped.range.contains(&address))
+            .map(|mapped| (mapped.device.as_mut(), address - mapped.range.start))
+    }
+    pub(crate) fn tick_devices(&mut self) {
+        for mapped in self.devices.values_mut() {
+            mapped.device.tick();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    use crate::{test_util::assemble, Device, SnapshotError, Vm, VmConfig, Word};
+
+    /// counts reads, writes replace the count
+    struct Counter(Arc<AtomicU32>);
+
+    impl Device for Counter {
+        fn peek(&self, _offset: Word) -> Word {
+            self.0.load(Ordering::Relaxed)
+        }
+        fn read(&mut self, offset: Word) -> Word {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            self.peek(offset)
+        }
+        fn write(&mut self, _offset: Word, value: Word) {
+            self.0.store(value, Ordering::Relaxed);
+        }
+        fn save_state(&self) -> Vec<u8> {
+            self.0.load(Ordering::Relaxed).to_be_bytes().to_vec()
+        }
+        fn restore_state(&mut self, state: &[u8]) -> Result<(), SnapshotError> {
+            let state = state
+                .try_into()
+                .map_err(|_| SnapshotError::UnexpectedEnd { len: 4 })?;
+            self.0.store(Word::from_be_bytes(state), Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn accesses_are_routed_to_devices() {
+        let program = assemble("mov r0, [0x100]\nmov r0, [0x100]\nmov [0x104], r0\nhlt");
+        let count = Arc::new(AtomicU32::new(0));
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x40));
+        let id = vm.map_device(0x100..0x108, Box::new(Counter(count.clone())));
+
+        vm.run(10);
+        assert_eq!(count.load(Ordering::Relaxed), 2);
+        assert_eq!(vm.memory_value(&0x100), Ok(2));
+
+        vm.set_memory_value(&0x100, 5).unwrap();
+        assert_eq!(count.load(Ordering::Relaxed), 5);
+        assert!(vm.unmap_device(id).is_some());
+        assert!(vm.memory_value(&0x100).is_err());
+    }
+
+    #[test]
+    fn snapshots_keep_device_state() {
+        let count = Arc::new(AtomicU32::new(3));
+        let mut vm = Vm::new(Vec::new(), VmConfig::new().memory_size(0x40));
+        vm.map_device(0x100..0x104, Box::new(Counter(count.clone())));
+        let snapshot = vm.snapshot();
+        vm.set_memory_value(&0x100, 9).unwrap();
+
+        vm.restore(&snapshot).unwrap();
+        assert_eq!(vm.memory_value(&0x100), Ok(3));
+
+        // restored into a new vm with the device mapped at the same range
+        let restored_count = Arc::new(AtomicU32::new(0));
+        let mut restored = Vm::new(Vec::new(), VmConfig::new().memory_size(0x40));
+        restored.map_device(0x100..0x104, Box::new(Counter(restored_count.clone())));
+        restored.restore(&snapshot).unwrap();
+        assert_eq!(restored_count.load(Ordering::Relaxed), 3);
+    }
+}
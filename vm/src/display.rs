@@ -0,0 +1,228 @@
+use std::fmt::{Display, Formatter, Result};
+
+use crate::vm::{
+    CallConfig, ConditionalJmpConfig, Config, Instruction, JmpConfig, JmpVariant, NotConfig,
+    PopConfig, PushConfig, Register,
+};
+
+impl Display for Register {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Register::GeneralPurpose0 => write!(f, "r0"),
+            Register::GeneralPurpose1 => write!(f, "r1"),
+            Register::Flag => write!(f, "fl"),
+            Register::ProgramCounter => write!(f, "pc"),
+        }
+    }
+}
+
+impl Display for Config {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Config::RegisterFromRegister(destination, source) => {
+                write!(f, "{destination}, {source}")
+            }
+            Config::RegisterFromImmediate(destination, source) => {
+                write!(f, "{destination}, {source:#X}")
+            }
+            Config::RegisterFromRegisterAddress(destination, source) => {
+                write!(f, "{destination}, [{source}]")
+            }
+            Config::RegisterFromImmediateAddress(destination, source) => {
+                write!(f, "{destination}, [{source:#X}]")
+            }
+            Config::RegisterAddressFromRegister(destination, source) => {
+                write!(f, "[{destination}], {source}")
+            }
+            Config::RegisterAddressFromImmediate(destination, source) => {
+                write!(f, "[{destination}], {source:#X}")
+            }
+            Config::ImmediateAddressFromRegister(destination, source) => {
+                write!(f, "[{destination:#X}], {source}")
+            }
+            Config::ImmediateAddressFromImmediate(destination, source) => {
+                write!(f, "[{destination:#X}], {source:#X}")
+            }
+            Config::ImmediateFromImmediate(destination, source) => {
+                write!(f, "{destination:#X}, {source:#X}")
+            }
+            Config::ImmediateFromRegister(destination, source) => {
+                write!(f, "{destination:#X}, {source}")
+            }
+        }
+    }
+}
+
+impl Display for JmpConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            JmpConfig::Register(register) => write!(f, "{register}"),
+            JmpConfig::Immediate(immediate) => write!(f, "{immediate:#X}"),
+            JmpConfig::RegisterAddress(register) => write!(f, "[{register}]"),
+            JmpConfig::ImmediateAddress(immediate) => write!(f, "[{immediate:#X}]"),
+        }
+    }
+}
+
+/// written before the target, so absolute jumps keep their plain syntax
+impl Display for JmpVariant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            JmpVariant::Absolute => Ok(()),
+            JmpVariant::Relative => write!(f, "rel "),
+        }
+    }
+}
+
+impl Display for ConditionalJmpConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            ConditionalJmpConfig::RegisterFromRegister(destination, source) => {
+                write!(f, "{destination}, {source}")
+            }
+            ConditionalJmpConfig::RegisterFromImmediate(destination, source) => {
+                write!(f, "{destination}, {source:#X}")
+            }
+            ConditionalJmpConfig::RegisterFromRegisterAddress(destination, source) => {
+                write!(f, "{destination}, [{source}]")
+            }
+            ConditionalJmpConfig::RegisterFromImmediateAddress(destination, source) => {
+                write!(f, "{destination}, [{source:#X}]")
+            }
+            ConditionalJmpConfig::ImmediateFromRegister(destination, source) => {
+                write!(f, "{destination:#X}, {source}")
+            }
+            ConditionalJmpConfig::ImmediateFromImmediate(destination, source) => {
+                write!(f, "{destination:#X}, {source:#X}")
+            }
+            ConditionalJmpConfig::ImmediateFromRegisterAddress(destination, source) => {
+                write!(f, "{destination:#X}, [{source}]")
+            }
+            ConditionalJmpConfig::ImmediateFromImmediateAddress(destination, source) => {
+                write!(f, "{destination:#X}, [{source:#X}]")
+            }
+            ConditionalJmpConfig::RegisterAddressFromRegister(destination, source) => {
+                write!(f, "[{destination}], {source}")
+            }
+            ConditionalJmpConfig::RegisterAddressFromImmediate(destination, source) => {
+                write!(f, "[{destination}], {source:#X}")
+            }
+            ConditionalJmpConfig::ImmediateAddressFromRegister(destination, source) => {
+                write!(f, "[{destination:#X}], {source}")
+            }
+            ConditionalJmpConfig::ImmediateAddressFromImmediate(destination, source) => {
+                write!(f, "[{destination:#X}], {source:#X}")
+            }
+        }
+    }
+}
+
+impl Display for NotConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            NotConfig::Register(register) => write!(f, "{register}"),
+            NotConfig::RegisterAddress(register) => write!(f, "[{register}]"),
+            NotConfig::ImmediateAddress(immediate) => write!(f, "[{immediate:#X}]"),
+        }
+    }
+}
+
+impl Display for PushConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            PushConfig::Register(register) => write!(f, "{register}"),
+            PushConfig::Immediate(immediate) => write!(f, "{immediate:#X}"),
+            PushConfig::RegisterAddress(register) => write!(f, "[{register}]"),
+            PushConfig::ImmediateAddress(immediate) => write!(f, "[{immediate:#X}]"),
+        }
+    }
+}
+
+impl Display for PopConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            PopConfig::Register(register) => write!(f, "{register}"),
+            PopConfig::RegisterAddress(register) => write!(f, "[{register}]"),
+            PopConfig::ImmediateAddress(immediate) => write!(f, "[{immediate:#X}]"),
+        }
+    }
+}
+
+impl Display for CallConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            CallConfig::Register(register) => write!(f, "{register}"),
+            CallConfig::Immediate(immediate) => write!(f, "{immediate:#X}"),
+            CallConfig::RegisterAddress(register) => write!(f, "[{register}]"),
+            CallConfig::ImmediateAddress(immediate) => write!(f, "[{immediate:#X}]"),
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Instruction::Nop => write!(f, "nop"),
+            Instruction::Hlt => write!(f, "hlt"),
+            Instruction::Mov(config) => write!(f, "mov {config}"),
+            Instruction::Not(config) => write!(f, "not {config}"),
+            Instruction::Or(config) => write!(f, "or {config}"),
+            Instruction::And(config) => write!(f, "and {config}"),
+            Instruction::Xor(config) => write!(f, "xor {config}"),
+            Instruction::Shl(config) => write!(f, "shl {config}"),
+            Instruction::Shr(config) => write!(f, "shr {config}"),
+            Instruction::Add(config) => write!(f, "add {config}"),
+            Instruction::Sub(config) => write!(f, "sub {config}"),
+            Instruction::Mul(config) => write!(f, "mul {config}"),
+            Instruction::IMul(config) => write!(f, "imul {config}"),
+            Instruction::Div(config) => write!(f, "div {config}"),
+            Instruction::IDiv(config) => write!(f, "idiv {config}"),
+            Instruction::Rem(config) => write!(f, "rem {config}"),
+            Instruction::Cmp(config) => write!(f, "cmp {config}"),
+            Instruction::Jmp(config, variant) => write!(f, "jmp {variant}{config}"),
+            Instruction::Jz(config, variant) => write!(f, "jz {variant}{config}"),
+            Instruction::Jnz(config, variant) => write!(f, "jnz {variant}{config}"),
+            Instruction::Push(config) => write!(f, "push {config}"),
+            Instruction::Pop(config) => write!(f, "pop {config}"),
+            Instruction::Call(config) => write!(f, "call {config}"),
+            Instruction::Ret => write!(f, "ret"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{decode, test_util::assemble};
+
+    #[test]
+    fn display_round_trips_through_assembler() {
+        let sources = [
+            "nop",
+            "hlt",
+            "mov r0, [0x1000]",
+            "mov [r1], 0x2A",
+            "mov [0x2034], pc",
+            "add fl, r1",
+            "not [r0]",
+            "not [0x10]",
+            "cmp 0x1, 0x2",
+            "jmp [0x202C]",
+            "jmp r1",
+            "jz 0x30, [r0]",
+            "jnz [0x40], 0x0",
+            "jmp rel 0xFFFFFFF8",
+            "jz rel r1, fl",
+            "push 0x2A",
+            "pop [r0]",
+            "call [0x40]",
+            "ret",
+        ];
+        for source in sources {
+            let bytes = assemble(source);
+            let (instruction, length) = decode(&bytes, 0).expect("valid instruction");
+            assert_eq!(length, bytes.len());
+            assert_eq!(instruction.to_string(), source);
+            assert_eq!(assemble(&instruction.to_string()), bytes);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt::Display;
+
+use crate::{arch::Word, region::AccessKind};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmError {
+    InvalidOpcode {
+        pc: Word,
+        opcode: u8,
+    },
+    InvalidSelectorCombo {
+        pc: Word,
+        opcode: u8,
+        selectors: u8,
+    },
+    /// the instruction at `pc` accessed a word past the end of memory,
+    /// `pc` is the last executed instruction for accesses from outside the vm
+    MemoryOutOfBounds {
+        pc: Word,
+        address: Word,
+        len: usize,
+    },
+    /// `pc` is past the end of memory, which is `len` bytes
+    OutOfInstructions {
+        pc: Word,
+        len: usize,
+    },
+    /// the instruction at `pc` continues past the end of memory after `len` bytes
+    TruncatedInstruction {
+        pc: Word,
+        len: usize,
+    },
+    /// the instruction at `pc` accessed `address` in a region which doesn't allow it
+    AccessViolation {
+        pc: Word,
+        address: Word,
+        access: AccessKind,
+    },
+    /// `div`, `idiv` or `rem` at `pc` divided by zero
+    DivideByZero {
+        pc: Word,
+    },
+    /// interrupts can only be raised on lines below [`INTERRUPT_LINES`](crate::INTERRUPT_LINES)
+    InvalidInterruptLine {
+        line: u8,
+    },
+    UnsupportedArchitecture,
+}
+
+impl Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VmError::InvalidOpcode { pc, opcode } => {
+                write!(f, "unrecognized instruction '{opcode:#04X}' at {pc:#010X}")
+            }
+            VmError::InvalidSelectorCombo {
+                pc,
+                opcode,
+                selectors,
+            } => write!(
+                f,
+                "invalid selector/destination combo '{selectors:#010b}' for instruction '{opcode:#04X}' at {pc:#010X}"
+            ),
+            VmError::MemoryOutOfBounds { pc, address, len } => write!(
+                f,
+                "instruction at {pc:#010X} cannot access memory word at {address:#010X}: memory is {len} bytes"
+            ),
+            VmError::OutOfInstructions { pc, len } => {
+                write!(f, "out of instructions: pc {pc:#010X} >= {len}")
+            }
+            VmError::TruncatedInstruction { pc, len } => {
+                write!(f, "instruction at {pc:#010X} is cut off by the end of memory after {len} bytes")
+            }
+            VmError::AccessViolation {
+                pc,
+                address,
+                access,
+            } => {
+                let access = match access {
+                    AccessKind::Read => "read",
+                    AccessKind::Write => "write",
+                    AccessKind::Execute => "execute",
+                };
+                write!(f, "instruction at {pc:#010X} cannot {access} memory at {address:#010X}")
+            }
+            VmError::DivideByZero { pc } => {
+                write!(f, "instruction at {pc:#010X} divided by zero")
+            }
+            VmError::InvalidInterruptLine { line } => {
+                write!(f, "interrupt line {line} doesn't exist")
+            }
+            VmError::UnsupportedArchitecture => {
+                write!(f, "architecture should support 32 bit word pointers")
+            }
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
+
+#[cfg(test)]
+mod test {
+    use super::VmError;
+    use crate::{StopReason, Vm, VmConfig};
+
+    fn fault(program: Vec<u8>) -> StopReason {
+        Vm::new(program, VmConfig::new().memory_size(0x40))
+            .run(10)
+            .0
+    }
+
+    #[test]
+    fn faults_carry_the_pc() {
+        // nop; 0xFF
+        let invalid_opcode = VmError::InvalidOpcode {
+            pc: 1,
+            opcode: 0xFF,
+        };
+        assert_eq!(fault(vec![0x00, 0xFF]), StopReason::Fault(invalid_opcode));
+        // nop; not 5
+        let invalid_combo = VmError::InvalidSelectorCombo {
+            pc: 1,
+            opcode: 0x06,
+            selectors: 0x40,
+        };
+        assert_eq!(
+            fault(vec![0x00, 0x06, 0x40]),
+            StopReason::Fault(invalid_combo)
+        );
+        // nop; mov r0, [0x100]
+        let out_of_bounds = VmError::MemoryOutOfBounds {
+            pc: 1,
+            address: 0x100,
+            len: 0x40,
+        };
+        assert_eq!(
+            fault(vec![0x00, 0x02, 0x30, 0x00, 0x00, 0x01, 0x00]),
+            StopReason::Fault(out_of_bounds)
+        );
+
+        let mut vm = Vm::new(vec![0x00], VmConfig::new().memory_size(1));
+        assert!(vm.run_next_instruction().is_ok());
+        assert_eq!(
+            vm.run_next_instruction(),
+            Err(VmError::OutOfInstructions { pc: 1, len: 1 })
+        );
+    }
+}
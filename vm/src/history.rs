@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+
+use crate::{arch::Word, interrupt::InterruptController, observer::VmObserver, vm::Vm};
+
+struct HistoryEntry {
+    /// `r0`, `r1`, `fl`, `pc` and the stack pointer before the instruction executed
+    registers: [Word; 5],
+    hlt_location: Option<Word>,
+    interrupts: InterruptController,
+    cycle_count: u64,
+    /// amount of words at the back of [`History::writes`] which were overwritten by the instruction
+    writes: usize,
+}
+
+/// the state overwritten by the most recently executed instructions, oldest first
+#[derive(Default)]
+pub(crate) struct History {
+    capacity: usize,
+    entries: VecDeque<HistoryEntry>,
+    /// addresses and old values of overwritten memory words
+    writes: VecDeque<(Word, Word)>,
+}
+
+impl History {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.capacity != 0
+    }
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.writes.clear();
+    }
+    fn truncate_front(&mut self) {
+        while self.entries.len() > self.capacity {
+            let entry = self
+                .entries
+                .pop_front()
+                .expect("more entries than capacity");
+            self.writes.drain(..entry.writes);
+        }
+    }
+}
+
+impl<O: VmObserver> Vm<O> {
+    /// remember the state overwritten by the last `capacity` instructions so they can be undone,
+    /// `0` disables recording, which is the default
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history.capacity = capacity;
+        self.history.truncate_front();
+    }
+    /// the amount of instructions which can currently be undone
+    pub fn history_len(&self) -> usize {
+        self.history.entries.len()
+    }
+    /// undoes up to `n` instructions, returning the amount undone
+    pub fn step_back(&mut self, n: u64) -> u64 {
+        let mut undone = 0;
+        while undone < n {
+            let Some(entry) = self.history.entries.pop_back() else {
+                break;
+            };
+            for _ in 0..entry.writes {
+                let (address, old) = self.history.writes.pop_back().expect("writes of entry");
+                self.set_memory_value(&address, old)
+                    .expect("address was written to before");
+            }
+            let [r0, r1, fl, pc, sp] = entry.registers;
+            self.registers.general_purpose_0 = r0;
+            self.registers.general_purpose_1 = r1;
+            self.registers.flag = fl;
+            self.registers.program_counter = pc;
+            self.registers.stack_pointer = sp;
+            self.hlt_location = entry.hlt_location;
+            // after the writes, which may have been to the controller
+            self.interrupts = entry.interrupts;
+            self.instruction_count -= 1;
+            self.cycle_count = entry.cycle_count;
+            undone += 1;
+        }
+        if undone > 0 {
+            // don't stop at a breakpoint on the instruction we just went back to
+            self.resumed_breakpoint = Some(self.registers.program_counter);
+            self.watchpoint_hit = None;
+        }
+        undone
+    }
+    /// undoes instructions until [`Vm::instruction_count`] is `instruction_count`,
+    /// returns `false` without changing anything if it isn't in the history
+    pub fn rewind_to(&mut self, instruction_count: u64) -> bool {
+        let Some(n) = self.instruction_count.checked_sub(instruction_count) else {
+            return false;
+        };
+        if n > self.history_len() as u64 {
+            return false;
+        }
+        self.step_back(n);
+        true
+    }
+    pub(crate) fn record_instruction(&mut self) {
+        if !self.history.is_enabled() {
+            return;
+        }
+        self.history.entries.push_back(HistoryEntry {
+            registers: [
+                self.registers.general_purpose_0,
+                self.registers.general_purpose_1,
+                self.registers.flag,
+                self.registers.program_counter,
+                self.registers.stack_pointer,
+            ],
+            hlt_location: self.hlt_location,
+            interrupts: self.interrupts,
+            cycle_count: self.cycle_count,
+            writes: 0,
+        });
+        self.history.truncate_front();
+    }
+    pub(crate) fn record_write(&mut self, address: Word, old: Word) {
+        let Some(entry) = self.history.entries.back_mut() else {
+            return;
+        };
+        entry.writes += 1;
+        self.history.writes.push_back((address, old));
+    }
+    pub(crate) fn clear_history(&mut self) {
+        self.history.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Register, Vm, VmConfig};
+
+    #[test]
+    fn step_back_restores_registers_and_memory() {
+        // mov [0x20], 5; mov r0, 1; hlt
+        let program = vec![
+            0x02, 0xD0, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x05, //
+            0x02, 0x10, 0x00, 0x00, 0x00, 0x01, //
+            0x01,
+        ];
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x40));
+        vm.set_history_capacity(2);
+        vm.run(10);
+        assert!(vm.is_halted());
+        assert_eq!(vm.instruction_count(), 3);
+        assert_eq!(vm.history_len(), 2);
+
+        assert!(!vm.rewind_to(0));
+        assert!(vm.rewind_to(1));
+        assert!(!vm.is_halted());
+        assert_eq!(vm.register_value(&Register::ProgramCounter), 10);
+        assert_eq!(vm.register_value(&Register::GeneralPurpose0), 0);
+        assert_eq!(vm.memory_value(&0x20), Ok(5));
+
+        vm.set_history_capacity(4);
+        vm.run(1);
+        assert_eq!(vm.step_back(5), 1);
+        assert_eq!(vm.register_value(&Register::ProgramCounter), 10);
+
+        vm.run(10);
+        assert_eq!(vm.register_value(&Register::GeneralPurpose0), 1);
+    }
+}
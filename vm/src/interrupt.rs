@@ -0,0 +1,242 @@
+use crate::{
+    arch::Word,
+    error::VmError,
+    observer::VmObserver,
+    snapshot::{Reader, SnapshotError},
+    vm::{Register, Vm},
+};
+
+pub const INTERRUPT_LINES: usize = 32;
+
+/// offsets of the interrupt controller's words from where it's mapped
+pub const INTERRUPT_MASK_OFFSET: Word = 0x00;
+pub const INTERRUPT_PENDING_OFFSET: Word = 0x04;
+pub const INTERRUPT_SAVED_PC_OFFSET: Word = 0x08;
+pub const INTERRUPT_SAVED_FLAGS_OFFSET: Word = 0x0C;
+pub const INTERRUPT_RETURN_OFFSET: Word = 0x10;
+pub const INTERRUPT_VECTORS_OFFSET: Word = 0x20;
+pub const INTERRUPT_CONTROLLER_SIZE: Word = INTERRUPT_VECTORS_OFFSET + INTERRUPT_LINES as Word * 4;
+
+/// delivers interrupts raised on up to 32 lines, mapped into memory as:
+/// - the enable mask, bit `n` enabling line `n`
+/// - pending interrupts, which can be written to raise or drop them
+/// - `pc` and `fl` saved when the current interrupt was delivered
+/// - the return word, reading `1` while an interrupt is handled,
+///   writing to it restores `pc` and `fl` and allows the next interrupt
+/// - the handler address of every line
+///
+/// the lowest enabled pending line is delivered before the next instruction,
+/// interrupts aren't nested
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct InterruptController {
+    base: Option<Word>,
+    mask: Word,
+    pending: Word,
+    saved_pc: Word,
+    saved_flags: Word,
+    handling: bool,
+    vectors: [Word; INTERRUPT_LINES],
+}
+
+impl InterruptController {
+    pub(crate) fn is_deliverable(&self) -> bool {
+        !self.handling && self.pending & self.mask != 0
+    }
+    pub(crate) fn offset(&self, address: Word) -> Option<Word> {
+        let offset = address.checked_sub(self.base?)?;
+        (offset < INTERRUPT_CONTROLLER_SIZE).then_some(offset)
+    }
+    pub(crate) fn peek(&self, offset: Word) -> Word {
+        match offset {
+            INTERRUPT_MASK_OFFSET => self.mask,
+            INTERRUPT_PENDING_OFFSET => self.pending,
+            INTERRUPT_SAVED_PC_OFFSET => self.saved_pc,
+            INTERRUPT_SAVED_FLAGS_OFFSET => self.saved_flags,
+            INTERRUPT_RETURN_OFFSET => self.handling.into(),
+            offset if offset >= INTERRUPT_VECTORS_OFFSET && offset % 4 == 0 => {
+                self.vectors[((offset - INTERRUPT_VECTORS_OFFSET) / 4) as usize]
+            }
+            _ => 0,
+        }
+    }
+    /// appends the base as `0` when unmapped or `1` followed by the address,
+    /// then the mask, pending lines, saved `pc` and `fl`, whether an interrupt is handled (`u8`)
+    /// and the vectors
+    pub(crate) fn write_to(&self, bytes: &mut Vec<u8>) {
+        match self.base {
+            Some(base) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&base.to_be_bytes());
+            }
+            None => bytes.push(0),
+        }
+        for word in [self.mask, self.pending, self.saved_pc, self.saved_flags] {
+            bytes.extend_from_slice(&word.to_be_bytes());
+        }
+        bytes.push(self.handling.into());
+        for vector in self.vectors {
+            bytes.extend_from_slice(&vector.to_be_bytes());
+        }
+    }
+    pub(crate) fn read(reader: &mut Reader) -> Result<Self, SnapshotError> {
+        let base = match reader.u8()? {
+            0 => None,
+            1 => Some(reader.u32()?),
+            state => return Err(SnapshotError::InvalidInterruptState(state)),
+        };
+        let [mask, pending, saved_pc, saved_flags] =
+            [reader.u32()?, reader.u32()?, reader.u32()?, reader.u32()?];
+        let handling = match reader.u8()? {
+            0 => false,
+            1 => true,
+            state => return Err(SnapshotError::InvalidInterruptState(state)),
+        };
+        let mut vectors = [0; INTERRUPT_LINES];
+        for vector in &mut vectors {
+            *vector = reader.u32()?;
+        }
+        Ok(Self {
+            base,
+            mask,
+            pending,
+            saved_pc,
+            saved_flags,
+            handling,
+            vectors,
+        })
+    }
+}
+
+impl<O: VmObserver> Vm<O> {
+    /// maps the interrupt controller's words at `base`, which unmapped can't deliver interrupts
+    pub fn map_interrupt_controller(&mut self, base: Word) {
+        self.interrupts.base = Some(base);
+    }
+    /// marks `line` as pending, delivering it once it's enabled and no other interrupt is handled
+    pub fn raise_interrupt(&mut self, line: u8) -> Result<(), VmError> {
+        if line as usize >= INTERRUPT_LINES {
+            return Err(VmError::InvalidInterruptLine { line });
+        }
+        self.interrupts.pending |= 1 << line;
+        Ok(())
+    }
+    pub(crate) fn write_interrupt_controller(&mut self, offset: Word, value: Word) {
+        let interrupts = &mut self.interrupts;
+        match offset {
+            INTERRUPT_MASK_OFFSET => interrupts.mask = value,
+            INTERRUPT_PENDING_OFFSET => interrupts.pending = value,
+            INTERRUPT_SAVED_PC_OFFSET => interrupts.saved_pc = value,
+            INTERRUPT_SAVED_FLAGS_OFFSET => interrupts.saved_flags = value,
+            INTERRUPT_RETURN_OFFSET if interrupts.handling => {
+                interrupts.handling = false;
+                let (pc, flags) = (interrupts.saved_pc, interrupts.saved_flags);
+                self.set_register_value(&Register::ProgramCounter, pc);
+                self.set_register_value(&Register::Flag, flags);
+            }
+            offset if offset >= INTERRUPT_VECTORS_OFFSET && offset % 4 == 0 => {
+                interrupts.vectors[((offset - INTERRUPT_VECTORS_OFFSET) / 4) as usize] = value;
+            }
+            _ => {}
+        }
+    }
+    /// jumps to the handler of the lowest enabled pending interrupt, saving `pc` and `fl`
+    pub(crate) fn deliver_interrupt(&mut self) {
+        if !self.interrupts.is_deliverable() {
+            return;
+        }
+        let line = (self.interrupts.pending & self.interrupts.mask).trailing_zeros() as usize;
+        self.interrupts.pending &= !(1 << line);
+        self.interrupts.handling = true;
+        self.interrupts.saved_pc = self.register_value(&Register::ProgramCounter);
+        self.interrupts.saved_flags = self.register_value(&Register::Flag);
+        // wakes the vm up if it's waiting after a `hlt`
+        self.hlt_location = None;
+        let handler = self.interrupts.vectors[line];
+        self.set_register_value(&Register::ProgramCounter, handler);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        test_util::assemble, ExternalInput, InputRecording, Register, StopReason, Vm, VmConfig,
+        VmError,
+    };
+
+    #[test]
+    fn interrupt_returns_to_the_interrupted_instruction() {
+        // the controller is mapped at 0x100, line 3 is handled by `handler`
+        let program = assemble(
+            "main:\n\
+             mov [0x100], 0b1000\n\
+             mov [0x12C], handler\n\
+             add r0, 1\n\
+             hlt\n\
+             add r0, 1\n\
+             hlt\n\
+             handler:\n\
+             add r1, 1\n\
+             mov [0x110], 0",
+        );
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x40));
+        vm.map_interrupt_controller(0x100);
+        assert_eq!(vm.run(10), (StopReason::Halted, 4));
+        assert_eq!(vm.run(10), (StopReason::Halted, 0));
+
+        vm.raise_interrupt(3).unwrap();
+        assert_eq!(vm.run(10), (StopReason::Halted, 4));
+        assert_eq!(vm.register_value(&Register::GeneralPurpose0), 2);
+        assert_eq!(vm.register_value(&Register::GeneralPurpose1), 1);
+        assert_eq!(vm.memory_value(&0x110), Ok(0));
+    }
+
+    #[test]
+    fn interrupt_state_is_recorded_and_undone() {
+        let program = assemble(
+            "main:\n\
+             mov [0x100], 0b1000\n\
+             mov [0x12C], handler\n\
+             hlt\n\
+             hlt\n\
+             handler:\n\
+             add r1, 1\n\
+             mov [0x110], 0",
+        );
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x40));
+        vm.map_interrupt_controller(0x100);
+        vm.set_history_capacity(8);
+        assert_eq!(vm.run(10), (StopReason::Halted, 3));
+
+        // interrupts were enabled before the recording started
+        vm.start_recording();
+        vm.inject(ExternalInput::Interrupt { line: 3 }).unwrap();
+        assert_eq!(vm.run(10), (StopReason::Halted, 3));
+        assert_eq!(vm.register_value(&Register::GeneralPurpose1), 1);
+        let recording = vm.stop_recording().unwrap();
+        let recording = InputRecording::from_bytes(&recording.to_bytes()).unwrap();
+
+        let mut replayed = Vm::new(Vec::new(), VmConfig::new().memory_size(0x10));
+        replayed.replay(&recording).unwrap();
+        assert_eq!(replayed.run(10), (StopReason::Halted, 3));
+        assert_eq!(replayed.register_value(&Register::GeneralPurpose1), 1);
+        assert_eq!(replayed.snapshot(), vm.snapshot());
+
+        // back inside the handler, before it returns
+        assert_eq!(vm.step_back(2), 2);
+        assert_eq!(vm.memory_value(&0x110), Ok(1));
+        assert_eq!(vm.run(10), (StopReason::Halted, 2));
+        assert_eq!(vm.memory_value(&0x110), Ok(0));
+    }
+
+    #[test]
+    fn raising_lines_past_the_last_one_fails() {
+        let mut vm = Vm::new(Vec::new(), VmConfig::new().memory_size(0x10));
+        vm.map_interrupt_controller(0x100);
+        assert_eq!(
+            vm.raise_interrupt(33),
+            Err(VmError::InvalidInterruptLine { line: 33 })
+        );
+        vm.raise_interrupt(31).unwrap();
+        assert_eq!(vm.memory_value(&0x104), Ok(1 << 31));
+    }
+}
@@ -1,4 +1,48 @@
+mod alu;
 mod arch;
+mod block;
+mod breakpoint;
+mod cache;
+mod config;
+mod coverage;
+mod cycles;
+mod decode;
+mod device;
+mod display;
+mod error;
+mod history;
+mod interrupt;
+mod memory;
 mod named_instruction;
+mod observer;
+mod profiler;
+mod region;
+mod replay;
+mod run;
+mod snapshot;
+#[cfg(test)]
+mod test_util;
 mod vm;
+mod watchpoint;
+pub use arch::Word;
+pub use block::ExecutionEngine;
+pub use breakpoint::*;
+pub use config::*;
+pub use coverage::*;
+pub use decode::*;
+pub use device::*;
+pub use error::*;
+pub use interrupt::{
+    INTERRUPT_CONTROLLER_SIZE, INTERRUPT_LINES, INTERRUPT_MASK_OFFSET, INTERRUPT_PENDING_OFFSET,
+    INTERRUPT_RETURN_OFFSET, INTERRUPT_SAVED_FLAGS_OFFSET, INTERRUPT_SAVED_PC_OFFSET,
+    INTERRUPT_VECTORS_OFFSET,
+};
+pub use memory::MemoryBackend;
+pub use observer::*;
+pub use profiler::*;
+pub use region::*;
+pub use replay::*;
+pub use run::*;
+pub use snapshot::*;
 pub use vm::*;
+pub use watchpoint::*;
@@ -0,0 +1,155 @@
+const PAGE_BITS: u32 = 12;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+
+type Page = [u8; PAGE_SIZE];
+
+/// how the vm stores its memory, chosen when it's constructed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryBackend {
+    /// every byte is allocated up front
+    #[default]
+    Flat,
+    /// 4 KiB pages are allocated when first written to and read as zeroes before that,
+    /// so large address spaces only cost the memory actually used
+    Paged,
+}
+
+pub(crate) enum Memory {
+    Flat(Vec<u8>),
+    Paged {
+        len: usize,
+        pages: Vec<Option<Box<Page>>>,
+    },
+}
+
+impl Memory {
+    pub(crate) fn new(backend: MemoryBackend, len: usize) -> Self {
+        match backend {
+            MemoryBackend::Flat => Self::Flat(vec![0; len]),
+            MemoryBackend::Paged => Self::Paged {
+                len,
+                pages: (0..len.div_ceil(PAGE_SIZE)).map(|_| None).collect(),
+            },
+        }
+    }
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Self::Flat(bytes) => bytes.len(),
+            Self::Paged { len, .. } => *len,
+        }
+    }
+    fn contains(&self, address: usize, len: usize) -> bool {
+        address
+            .checked_add(len)
+            .is_some_and(|end| end <= self.len())
+    }
+    /// the word at `address`, `None` if it's out of bounds
+    #[inline]
+    pub(crate) fn read_word(&self, address: usize) -> Option<[u8; 4]> {
+        match self {
+            Self::Flat(bytes) => bytes.get(address..address.checked_add(4)?)?.try_into().ok(),
+            Self::Paged { .. } => {
+                let mut word = [0; 4];
+                self.read(address, &mut word).then_some(word)
+            }
+        }
+    }
+    /// writes the word at `address`, returns `false` without writing if it's out of bounds
+    #[inline]
+    pub(crate) fn write_word(&mut self, address: usize, word: [u8; 4]) -> bool {
+        match self {
+            Self::Flat(bytes) => {
+                let Some(end) = address.checked_add(4) else {
+                    return false;
+                };
+                let Some(target) = bytes.get_mut(address..end) else {
+                    return false;
+                };
+                target.copy_from_slice(&word);
+                true
+            }
+            Self::Paged { .. } => self.write(address, &word),
+        }
+    }
+    /// fills `buffer` with the bytes starting at `address`, returns `false` if they're out of bounds
+    pub(crate) fn read(&self, address: usize, buffer: &mut [u8]) -> bool {
+        if !self.contains(address, buffer.len()) {
+            return false;
+        }
+        match self {
+            Self::Flat(bytes) => buffer.copy_from_slice(&bytes[address..address + buffer.len()]),
+            Self::Paged { pages, .. } => {
+                let mut done = 0;
+                while done < buffer.len() {
+                    let (index, offset) =
+                        ((address + done) >> PAGE_BITS, (address + done) % PAGE_SIZE);
+                    let len = (PAGE_SIZE - offset).min(buffer.len() - done);
+                    let target = &mut buffer[done..done + len];
+                    match &pages[index] {
+                        Some(page) => target.copy_from_slice(&page[offset..offset + len]),
+                        None => target.fill(0),
+                    }
+                    done += len;
+                }
+            }
+        }
+        true
+    }
+    /// writes `bytes` starting at `address`, returns `false` without writing if they're out of bounds
+    pub(crate) fn write(&mut self, address: usize, bytes: &[u8]) -> bool {
+        if !self.contains(address, bytes.len()) {
+            return false;
+        }
+        match self {
+            Self::Flat(memory) => memory[address..address + bytes.len()].copy_from_slice(bytes),
+            Self::Paged { pages, .. } => {
+                let mut done = 0;
+                while done < bytes.len() {
+                    let (index, offset) =
+                        ((address + done) >> PAGE_BITS, (address + done) % PAGE_SIZE);
+                    let len = (PAGE_SIZE - offset).min(bytes.len() - done);
+                    let page = pages[index].get_or_insert_with(|| Box::new([0; PAGE_SIZE]));
+                    page[offset..offset + len].copy_from_slice(&bytes[done..done + len]);
+                    done += len;
+                }
+            }
+        }
+        true
+    }
+    /// the allocated parts of memory and their addresses in order, everything else is zeroed
+    pub(crate) fn regions(&self) -> Box<dyn Iterator<Item = (usize, &[u8])> + '_> {
+        match self {
+            Self::Flat(bytes) => Box::new(std::iter::once((0, bytes.as_slice()))),
+            Self::Paged { len, pages } => {
+                Box::new(pages.iter().enumerate().filter_map(move |(index, page)| {
+                    let address = index << PAGE_BITS;
+                    let end = (address + PAGE_SIZE).min(*len);
+                    Some((address, &page.as_ref()?[..end - address]))
+                }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Memory, MemoryBackend, PAGE_SIZE};
+
+    #[test]
+    fn paged_memory_allocates_written_pages() {
+        let mut memory = Memory::new(MemoryBackend::Paged, 0xFFFF_FFFF);
+        let mut bytes = [0xFF; 4];
+        assert!(memory.read(0x1234_5678, &mut bytes));
+        assert_eq!(bytes, [0; 4]);
+
+        let address = 3 * PAGE_SIZE - 2;
+        assert!(memory.write(address, &[1, 2, 3, 4]));
+        assert!(memory.read(address, &mut bytes));
+        assert_eq!(bytes, [1, 2, 3, 4]);
+        let regions: Vec<_> = memory.regions().map(|(address, _)| address).collect();
+        assert_eq!(regions, [2 * PAGE_SIZE, 3 * PAGE_SIZE]);
+
+        assert!(!memory.write(0xFFFF_FFFD, &[0; 4]));
+        assert!(!memory.read(usize::MAX, &mut bytes));
+    }
+}
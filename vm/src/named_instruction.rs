@@ -20,12 +20,20 @@ pub enum NamedInstruction {
     Jmp,
     Jz,
     Jnz,
+    JmpRel,
+    JzRel,
+    JnzRel,
+    Push,
+    Pop,
+    Call,
+    Ret,
 }
 
 pub use NamedInstruction::*;
 
 impl TryFrom<u8> for NamedInstruction {
-    type Error = String;
+    /// the unrecognized opcode
+    type Error = u8;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
@@ -49,7 +57,14 @@ impl TryFrom<u8> for NamedInstruction {
             0x11 => Ok(NamedInstruction::Jmp),
             0x12 => Ok(NamedInstruction::Jz),
             0x13 => Ok(NamedInstruction::Jnz),
-            instruction => Err(format!("unrecognized instruction '0x{instruction:X}'")),
+            0x14 => Ok(NamedInstruction::JmpRel),
+            0x15 => Ok(NamedInstruction::JzRel),
+            0x16 => Ok(NamedInstruction::JnzRel),
+            0x17 => Ok(NamedInstruction::Push),
+            0x18 => Ok(NamedInstruction::Pop),
+            0x19 => Ok(NamedInstruction::Call),
+            0x1A => Ok(NamedInstruction::Ret),
+            opcode => Err(opcode),
         }
     }
 }
@@ -0,0 +1,148 @@
+use crate::{
+    arch::Word,
+    vm::{Instruction, Register},
+};
+
+/// hooks into execution, for example to build tracers, profilers or coverage tools
+///
+/// every callback defaults to doing nothing, the `()` observer is used when none is attached
+pub trait VmObserver {
+    /// whether the vm should gather the values passed to the callbacks,
+    /// `false` lets the vm skip e.g. reading the old value of overwritten memory
+    fn is_enabled(&self) -> bool {
+        true
+    }
+    /// called before the instruction at `pc` is executed
+    fn on_instruction(&mut self, _pc: Word, _instruction: &Instruction) {}
+    fn on_memory_read(&mut self, _address: Word, _value: Word) {}
+    fn on_memory_write(&mut self, _address: Word, _old: Word, _new: Word) {}
+    fn on_register_write(&mut self, _register: &Register, _old: Word, _new: Word) {}
+    /// called once the conditional jump at `pc` decided whether to jump
+    fn on_branch(&mut self, _pc: Word, _taken: bool) {}
+}
+
+impl VmObserver for () {
+    fn is_enabled(&self) -> bool {
+        false
+    }
+}
+
+impl<T: VmObserver> VmObserver for Option<T> {
+    fn is_enabled(&self) -> bool {
+        self.as_ref().is_some_and(VmObserver::is_enabled)
+    }
+    fn on_instruction(&mut self, pc: Word, instruction: &Instruction) {
+        if let Some(observer) = self {
+            observer.on_instruction(pc, instruction);
+        }
+    }
+    fn on_memory_read(&mut self, address: Word, value: Word) {
+        if let Some(observer) = self {
+            observer.on_memory_read(address, value);
+        }
+    }
+    fn on_memory_write(&mut self, address: Word, old: Word, new: Word) {
+        if let Some(observer) = self {
+            observer.on_memory_write(address, old, new);
+        }
+    }
+    fn on_register_write(&mut self, register: &Register, old: Word, new: Word) {
+        if let Some(observer) = self {
+            observer.on_register_write(register, old, new);
+        }
+    }
+    fn on_branch(&mut self, pc: Word, taken: bool) {
+        if let Some(observer) = self {
+            observer.on_branch(pc, taken);
+        }
+    }
+}
+
+impl<A: VmObserver, B: VmObserver> VmObserver for (A, B) {
+    fn is_enabled(&self) -> bool {
+        self.0.is_enabled() || self.1.is_enabled()
+    }
+    fn on_instruction(&mut self, pc: Word, instruction: &Instruction) {
+        self.0.on_instruction(pc, instruction);
+        self.1.on_instruction(pc, instruction);
+    }
+    fn on_memory_read(&mut self, address: Word, value: Word) {
+        self.0.on_memory_read(address, value);
+        self.1.on_memory_read(address, value);
+    }
+    fn on_memory_write(&mut self, address: Word, old: Word, new: Word) {
+        self.0.on_memory_write(address, old, new);
+        self.1.on_memory_write(address, old, new);
+    }
+    fn on_register_write(&mut self, register: &Register, old: Word, new: Word) {
+        self.0.on_register_write(register, old, new);
+        self.1.on_register_write(register, old, new);
+    }
+    fn on_branch(&mut self, pc: Word, taken: bool) {
+        self.0.on_branch(pc, taken);
+        self.1.on_branch(pc, taken);
+    }
+}
+
+impl<T: VmObserver + ?Sized> VmObserver for Box<T> {
+    fn is_enabled(&self) -> bool {
+        self.as_ref().is_enabled()
+    }
+    fn on_instruction(&mut self, pc: Word, instruction: &Instruction) {
+        self.as_mut().on_instruction(pc, instruction);
+    }
+    fn on_memory_read(&mut self, address: Word, value: Word) {
+        self.as_mut().on_memory_read(address, value);
+    }
+    fn on_memory_write(&mut self, address: Word, old: Word, new: Word) {
+        self.as_mut().on_memory_write(address, old, new);
+    }
+    fn on_register_write(&mut self, register: &Register, old: Word, new: Word) {
+        self.as_mut().on_register_write(register, old, new);
+    }
+    fn on_branch(&mut self, pc: Word, taken: bool) {
+        self.as_mut().on_branch(pc, taken);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Instruction, Register, Vm, VmConfig, VmObserver, Word};
+
+    #[derive(Default)]
+    struct Recorder {
+        instructions: Vec<Word>,
+        writes: Vec<(Word, Word, Word)>,
+        registers: usize,
+    }
+
+    impl VmObserver for Recorder {
+        fn on_instruction(&mut self, pc: Word, _instruction: &Instruction) {
+            self.instructions.push(pc);
+        }
+        fn on_memory_write(&mut self, address: Word, old: Word, new: Word) {
+            self.writes.push((address, old, new));
+        }
+        fn on_register_write(&mut self, _register: &Register, _old: Word, _new: Word) {
+            self.registers += 1;
+        }
+    }
+
+    #[test]
+    fn observer_sees_instructions_and_writes() {
+        // mov [0x20], 5; mov r0, 1; hlt
+        let program = vec![
+            0x02, 0xD0, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x05, //
+            0x02, 0x10, 0x00, 0x00, 0x00, 0x01, //
+            0x01,
+        ];
+        let mut vm =
+            Vm::new(program, VmConfig::new().memory_size(0x40)).with_observer(Recorder::default());
+        vm.run(10);
+
+        let observer = vm.observer();
+        assert_eq!(observer.instructions, vec![0, 10, 16]);
+        assert_eq!(observer.writes, vec![(0x20, 0, 5)]);
+        assert_eq!(observer.registers, 1);
+    }
+}
@@ -0,0 +1,154 @@
+use std::{cmp::Reverse, collections::BTreeMap, fmt::Write};
+
+use crate::{arch::Word, observer::VmObserver, vm::Instruction};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InstructionProfile {
+    pub executions: u64,
+    /// see [`Instruction::cycles`]
+    pub cycles: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryProfile {
+    /// words read by instructions starting at the address
+    pub reads: u64,
+    pub writes: u64,
+}
+
+impl MemoryProfile {
+    pub fn accesses(&self) -> u64 {
+        self.reads + self.writes
+    }
+}
+
+/// counts where a program spends its time, attach it with [`Vm::with_observer`](crate::Vm::with_observer)
+#[derive(Debug, Default, Clone)]
+pub struct Profiler {
+    instructions: BTreeMap<Word, InstructionProfile>,
+    memory: BTreeMap<Word, MemoryProfile>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// executed instruction addresses in order
+    pub fn instructions(&self) -> impl Iterator<Item = (Word, &InstructionProfile)> {
+        self.instructions
+            .iter()
+            .map(|(address, profile)| (*address, profile))
+    }
+    /// memory addresses accessed by instructions in order, devices included
+    pub fn memory(&self) -> impl Iterator<Item = (Word, &MemoryProfile)> {
+        self.memory
+            .iter()
+            .map(|(address, profile)| (*address, profile))
+    }
+    pub fn total_cycles(&self) -> u64 {
+        self.instructions
+            .values()
+            .map(|profile| profile.cycles)
+            .sum()
+    }
+    /// instruction addresses with the most cycles first
+    pub fn hottest_instructions(&self) -> Vec<(Word, InstructionProfile)> {
+        let mut hottest: Vec<_> = self
+            .instructions()
+            .map(|(address, profile)| (address, *profile))
+            .collect();
+        hottest.sort_by_key(|(address, profile)| (Reverse(profile.cycles), *address));
+        hottest
+    }
+    /// memory addresses with the most accesses first
+    pub fn hottest_memory(&self) -> Vec<(Word, MemoryProfile)> {
+        let mut hottest: Vec<_> = self
+            .memory()
+            .map(|(address, profile)| (address, *profile))
+            .collect();
+        hottest.sort_by_key(|(address, profile)| (Reverse(profile.accesses()), *address));
+        hottest
+    }
+    /// one line per address, ordered by address:
+    /// `instruction <address> <executions> <cycles>` followed by `memory <address> <reads> <writes>`
+    pub fn export(&self) -> String {
+        let mut out = String::new();
+        for (address, profile) in self.instructions() {
+            let InstructionProfile { executions, cycles } = profile;
+            writeln!(out, "instruction {address:#010X} {executions} {cycles}")
+                .expect("writing to a string");
+        }
+        for (address, profile) in self.memory() {
+            let MemoryProfile { reads, writes } = profile;
+            writeln!(out, "memory {address:#010X} {reads} {writes}").expect("writing to a string");
+        }
+        out
+    }
+}
+
+impl VmObserver for Profiler {
+    fn on_instruction(&mut self, pc: Word, instruction: &Instruction) {
+        let profile = self.instructions.entry(pc).or_default();
+        profile.executions += 1;
+        profile.cycles += instruction.cycles();
+    }
+    fn on_memory_read(&mut self, address: Word, _value: Word) {
+        self.memory.entry(address).or_default().reads += 1;
+    }
+    fn on_memory_write(&mut self, address: Word, _old: Word, _new: Word) {
+        self.memory.entry(address).or_default().writes += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InstructionProfile, MemoryProfile, Profiler};
+    use crate::{test_util::assemble, StopReason, Vm, VmConfig};
+
+    #[test]
+    fn loops_are_the_hottest_instructions() {
+        let program = assemble(
+            "main:\n\
+             mov r0, 3\n\
+             .loop:\n\
+             add [0x40], 2\n\
+             sub r0, 1\n\
+             jnz .loop, r0\n\
+             hlt",
+        );
+        let mut vm =
+            Vm::new(program, VmConfig::new().memory_size(0x80)).with_observer(Profiler::new());
+        assert_eq!(vm.run(100).0, StopReason::Halted);
+
+        let profiler = vm.observer();
+        let hottest = profiler.hottest_instructions();
+        let add = InstructionProfile {
+            executions: 3,
+            cycles: 9,
+        };
+        assert_eq!(hottest[0], (0x06, add));
+        assert_eq!(hottest.len(), 5);
+        assert_eq!(profiler.total_cycles(), vm.cycle_count());
+        let memory = MemoryProfile {
+            reads: 3,
+            writes: 3,
+        };
+        assert_eq!(profiler.hottest_memory(), vec![(0x40, memory)]);
+        assert!(profiler
+            .export()
+            .starts_with("instruction 0x00000000 1 1\ninstruction 0x00000006 3 9\n"));
+    }
+
+    #[test]
+    fn stores_only_count_as_writes() {
+        let program = assemble("mov [0x40], 5\nhlt");
+        let mut vm =
+            Vm::new(program, VmConfig::new().memory_size(0x80)).with_observer(Profiler::new());
+        assert_eq!(vm.run(10).0, StopReason::Halted);
+        let memory = MemoryProfile {
+            reads: 0,
+            writes: 1,
+        };
+        assert_eq!(vm.observer().hottest_memory(), vec![(0x40, memory)]);
+    }
+}
@@ -0,0 +1,227 @@
+use std::ops::Range;
+
+use crate::{arch::Word, error::VmError, observer::VmObserver, vm::Vm};
+
+/// what the program may do with the memory in a region
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permissions {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Permissions {
+    /// code and rom
+    pub const READ_ONLY: Self = Self {
+        read: true,
+        write: false,
+        execute: true,
+    };
+    /// data, e.g. vram
+    pub const NO_EXECUTE: Self = Self {
+        read: true,
+        write: true,
+        execute: false,
+    };
+    pub const UNMAPPED: Self = Self {
+        read: false,
+        write: false,
+        execute: false,
+    };
+    fn allows(&self, access: AccessKind) -> bool {
+        match access {
+            AccessKind::Read => self.read,
+            AccessKind::Write => self.write,
+            AccessKind::Execute => self.execute,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    Execute,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub range: Range<Word>,
+    pub permissions: Permissions,
+}
+
+impl<O: VmObserver> Vm<O> {
+    /// restricts accesses touching any byte in `range`, returns an id used to remove the region
+    ///
+    /// memory outside of regions can be read, written and executed,
+    /// regions added later take precedence where ranges overlap
+    pub fn add_region(&mut self, range: Range<Word>, permissions: Permissions) -> usize {
+        let id = self.next_region_id;
+        self.next_region_id += 1;
+        self.regions.insert(id, Region { range, permissions });
+        self.drop_blocks();
+        id
+    }
+    /// returns whether a region with `id` existed
+    pub fn remove_region(&mut self, id: usize) -> bool {
+        let removed = self.regions.remove(&id).is_some();
+        self.drop_blocks();
+        removed
+    }
+    /// regions sorted by id
+    pub fn regions(&self) -> impl Iterator<Item = (usize, &Region)> {
+        self.regions.iter().map(|(id, region)| (*id, region))
+    }
+    fn is_byte_allowed(&self, address: Word, access: AccessKind) -> bool {
+        self.regions
+            .values()
+            .rev()
+            .find(|region| region.range.contains(&address))
+            .is_none_or(|region| region.permissions.allows(access))
+    }
+    /// the first of the `len` bytes from `address` which may not be accessed
+    fn first_denied(&self, address: Word, len: Word, access: AccessKind) -> Option<Word> {
+        (0..len)
+            .map(|offset| address.wrapping_add(offset))
+            .find(|address| !self.is_byte_allowed(*address, access))
+    }
+    pub(crate) fn is_allowed(&self, address: Word, len: Word, access: AccessKind) -> bool {
+        self.first_denied(address, len, access).is_none()
+    }
+    /// faults if the instruction at `pc` may not access any of the `len` bytes from `address`,
+    /// reporting the first byte it may not access
+    pub(crate) fn check_access(
+        &self,
+        pc: Word,
+        address: Word,
+        len: Word,
+        access: AccessKind,
+    ) -> Result<(), VmError> {
+        if self.regions.is_empty() {
+            return Ok(());
+        }
+        match self.first_denied(address, len, access) {
+            None => Ok(()),
+            Some(address) => Err(VmError::AccessViolation {
+                pc,
+                address,
+                access,
+            }),
+        }
+    }
+    /// drops decoded blocks, which were checked against the previous regions
+    fn drop_blocks(&mut self) {
+        let engine = self.execution_engine();
+        self.set_execution_engine(engine);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        test_util::assemble, AccessKind, ExecutionEngine, Permissions, Register, StopReason, Vm,
+        VmConfig, VmError,
+    };
+
+    #[test]
+    fn runaway_writes_fault_instead_of_overwriting_code() {
+        let program = assemble(
+            "main:\n\
+             mov r1, 0x40\n\
+             .loop:\n\
+             sub r1, 4\n\
+             mov [r1], 0\n\
+             jmp .loop",
+        );
+        for engine in [ExecutionEngine::Interpreter, ExecutionEngine::BasicBlock] {
+            let mut vm = Vm::new(program.clone(), VmConfig::new().memory_size(0x40));
+            vm.set_execution_engine(engine);
+            vm.add_region(0..program.len() as u32, Permissions::READ_ONLY);
+            let fault = VmError::AccessViolation {
+                pc: 0x0C,
+                address: 0x14,
+                access: AccessKind::Write,
+            };
+            assert_eq!(vm.run(100).0, StopReason::Fault(fault));
+            let code = u32::from_be_bytes(program[0x14..0x18].try_into().unwrap());
+            assert_eq!(vm.memory_value(&0x14), Ok(code));
+        }
+    }
+
+    #[test]
+    fn data_regions_cant_be_executed_or_read_when_unmapped() {
+        let program = assemble("mov r0, [0x30]\nmov pc, 0x20");
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x40));
+        let unmapped = vm.add_region(0x30..0x34, Permissions::UNMAPPED);
+        vm.add_region(0x20..0x30, Permissions::NO_EXECUTE);
+        let fault = VmError::AccessViolation {
+            pc: 0,
+            address: 0x30,
+            access: AccessKind::Read,
+        };
+        assert_eq!(vm.run(10).0, StopReason::Fault(fault));
+
+        assert!(vm.remove_region(unmapped));
+        vm.set_register_value(&Register::ProgramCounter, 0);
+        let fault = VmError::AccessViolation {
+            pc: 0x20,
+            address: 0x20,
+            access: AccessKind::Execute,
+        };
+        assert_eq!(vm.run(10), (StopReason::Fault(fault), 2));
+    }
+
+    #[test]
+    fn write_only_regions_can_be_stored_to() {
+        let program = assemble("mov [0x30], 7\nmov r0, [0x30]");
+        let write_only = Permissions {
+            read: false,
+            write: true,
+            execute: false,
+        };
+        for engine in [ExecutionEngine::Interpreter, ExecutionEngine::BasicBlock] {
+            let mut vm = Vm::new(program.clone(), VmConfig::new().memory_size(0x40));
+            vm.set_execution_engine(engine);
+            vm.add_region(0x30..0x34, write_only);
+            let fault = VmError::AccessViolation {
+                pc: 0x0A,
+                address: 0x30,
+                access: AccessKind::Read,
+            };
+            assert_eq!(vm.run(10), (StopReason::Fault(fault), 1));
+            assert_eq!(vm.memory_value(&0x30), Ok(7));
+        }
+    }
+
+    #[test]
+    fn every_byte_of_an_access_is_checked() {
+        let program = assemble("mov [0x0E], 0xFFFFFFFF\nhlt");
+        let mut vm = Vm::new(program.clone(), VmConfig::new().memory_size(0x40));
+        vm.add_region(0x10..0x20, Permissions::READ_ONLY);
+        let fault = VmError::AccessViolation {
+            pc: 0,
+            address: 0x10,
+            access: AccessKind::Write,
+        };
+        assert_eq!(vm.run(10).0, StopReason::Fault(fault));
+        assert_eq!(vm.memory_value(&0x10), Ok(0));
+        // regions only restrict the program
+        assert_eq!(vm.set_memory_value(&0x10, 5), Ok(()));
+        assert_eq!(vm.memory_value(&0x10), Ok(5));
+
+        // `mov r0, 1` reaches into the region from byte 4
+        let program = assemble("mov r0, 1\nhlt");
+        for engine in [ExecutionEngine::Interpreter, ExecutionEngine::BasicBlock] {
+            let mut vm = Vm::new(program.clone(), VmConfig::new().memory_size(0x40));
+            vm.set_execution_engine(engine);
+            vm.add_region(0x04..0x08, Permissions::NO_EXECUTE);
+            let fault = VmError::AccessViolation {
+                pc: 0,
+                address: 0x04,
+                access: AccessKind::Execute,
+            };
+            assert_eq!(vm.run(10), (StopReason::Fault(fault), 0));
+            assert_eq!(vm.register_value(&Register::GeneralPurpose0), 0);
+        }
+    }
+}
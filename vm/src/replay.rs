@@ -0,0 +1,314 @@
+use crate::{
+    arch::Word,
+    error::VmError,
+    observer::VmObserver,
+    snapshot::{Reader, Snapshot, SnapshotError},
+    vm::{Register, Vm},
+};
+
+const MAGIC: &[u8; 4] = b"VC2R";
+pub const RECORDING_VERSION: u32 = 1;
+
+/// a change made to the machine from outside of the running program, e.g. by peripherals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExternalInput {
+    Memory { address: Word, value: Word },
+    Register { register: Register, value: Word },
+    Interrupt { line: u8 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    /// [`Vm::instruction_count`] when the input was injected
+    pub instruction_count: u64,
+    pub input: ExternalInput,
+}
+
+/// the state of the machine when recording started and every input injected since
+///
+/// encoded as big endian, in order:
+/// - `VC2R` and the format version (`u32`)
+/// - the instruction and cycle count when recording started (`u64` each)
+/// - the length of the snapshot (`u64`) and the snapshot itself
+/// - the amount of events (`u32`)
+/// - every event as its instruction count (`u64`), followed by either
+///   `0`, the address (`u32`) and the value (`u32`) for memory,
+///   `1`, the register (`u8`) and the value (`u32`) for registers,
+///   or `2` and the line (`u8`) for interrupts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputRecording {
+    pub instruction_count: u64,
+    pub cycle_count: u64,
+    pub start: Snapshot,
+    pub events: Vec<InputEvent>,
+}
+
+pub(crate) struct Replay {
+    events: Vec<InputEvent>,
+    next: usize,
+}
+
+fn register_code(register: Register) -> u8 {
+    match register {
+        Register::GeneralPurpose0 => 0b00,
+        Register::GeneralPurpose1 => 0b01,
+        Register::Flag => 0b10,
+        Register::ProgramCounter => 0b11,
+    }
+}
+
+impl InputRecording {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&RECORDING_VERSION.to_be_bytes());
+        bytes.extend_from_slice(&self.instruction_count.to_be_bytes());
+        bytes.extend_from_slice(&self.cycle_count.to_be_bytes());
+        let start = self.start.to_bytes();
+        bytes.extend_from_slice(&(start.len() as u64).to_be_bytes());
+        bytes.extend_from_slice(&start);
+        bytes.extend_from_slice(&(self.events.len() as u32).to_be_bytes());
+        for event in &self.events {
+            bytes.extend_from_slice(&event.instruction_count.to_be_bytes());
+            match event.input {
+                ExternalInput::Memory { address, value } => {
+                    bytes.push(0);
+                    bytes.extend_from_slice(&address.to_be_bytes());
+                    bytes.extend_from_slice(&value.to_be_bytes());
+                }
+                ExternalInput::Register { register, value } => {
+                    bytes.push(1);
+                    bytes.push(register_code(register));
+                    bytes.extend_from_slice(&value.to_be_bytes());
+                }
+                ExternalInput::Interrupt { line } => {
+                    bytes.push(2);
+                    bytes.push(line);
+                }
+            }
+        }
+        bytes
+    }
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+        let version = reader.u32()?;
+        if version != RECORDING_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let instruction_count = reader.u64()?;
+        let cycle_count = reader.u64()?;
+        let start_len = reader
+            .u64()?
+            .try_into()
+            .map_err(|_| SnapshotError::UnsupportedArchitecture)?;
+        let start = Snapshot::from_bytes(reader.take(start_len)?)?;
+        let event_count = reader.u32()?;
+        let mut events = Vec::new();
+        for _ in 0..event_count {
+            let instruction_count = reader.u64()?;
+            let input = match reader.u8()? {
+                0 => ExternalInput::Memory {
+                    address: reader.u32()?,
+                    value: reader.u32()?,
+                },
+                1 => {
+                    let code = reader.u8()?;
+                    let register =
+                        Register::try_from(code).map_err(|_| SnapshotError::InvalidInput(code))?;
+                    ExternalInput::Register {
+                        register,
+                        value: reader.u32()?,
+                    }
+                }
+                2 => ExternalInput::Interrupt { line: reader.u8()? },
+                kind => return Err(SnapshotError::InvalidInput(kind)),
+            };
+            events.push(InputEvent {
+                instruction_count,
+                input,
+            });
+        }
+        Ok(Self {
+            instruction_count,
+            cycle_count,
+            start,
+            events,
+        })
+    }
+}
+
+impl<O: VmObserver> Vm<O> {
+    /// applies `input` on behalf of something outside of the program, recording it if a recording is running
+    ///
+    /// ignored while replaying, so the replayed inputs are the only ones reaching the program
+    pub fn inject(&mut self, input: ExternalInput) -> Result<(), VmError> {
+        if self.replay.is_some() {
+            return Ok(());
+        }
+        self.apply_input(input)?;
+        if let Some(recording) = &mut self.recording {
+            recording.events.push(InputEvent {
+                instruction_count: self.instruction_count,
+                input,
+            });
+        }
+        Ok(())
+    }
+    fn apply_input(&mut self, input: ExternalInput) -> Result<(), VmError> {
+        match input {
+            ExternalInput::Memory { address, value } => {
+                // regions only restrict the program, so inputs can't fault on replay
+                let old = self.memory_value(&address)?;
+                self.set_memory_value(&address, value)?;
+                // undone together with the instruction before it
+                self.record_write(address, old);
+            }
+            ExternalInput::Register { register, value } => {
+                self.set_register_value(&register, value);
+            }
+            ExternalInput::Interrupt { line } => self.raise_interrupt(line)?,
+        }
+        Ok(())
+    }
+    /// starts recording injected inputs from the current state, replacing any running recording
+    pub fn start_recording(&mut self) {
+        self.recording = Some(InputRecording {
+            instruction_count: self.instruction_count,
+            cycle_count: self.cycle_count,
+            start: self.snapshot(),
+            events: Vec::new(),
+        });
+    }
+    pub fn stop_recording(&mut self) -> Option<InputRecording> {
+        self.recording.take()
+    }
+    /// restores the start of `recording` and re-injects its inputs at the same instruction counts,
+    /// injections from elsewhere are ignored until every recorded input is replayed
+    ///
+    /// # Errors
+    /// if the start of `recording` can't be restored
+    pub fn replay(&mut self, recording: &InputRecording) -> Result<(), SnapshotError> {
+        self.restore(&recording.start)?;
+        self.instruction_count = recording.instruction_count;
+        self.cycle_count = recording.cycle_count;
+        self.replay = Some(Replay {
+            events: recording.events.clone(),
+            next: 0,
+        });
+        Ok(())
+    }
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+    /// injects the recorded inputs due before the next instruction
+    pub(crate) fn replay_inputs(&mut self) -> Result<(), VmError> {
+        while let Some(replay) = &mut self.replay {
+            let Some(event) = replay.events.get(replay.next).copied() else {
+                self.replay = None;
+                break;
+            };
+            if event.instruction_count > self.instruction_count {
+                break;
+            }
+            replay.next += 1;
+            self.apply_input(event.input)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        test_util::assemble, ExternalInput, InputRecording, Permissions, Register, StopReason, Vm,
+        VmConfig,
+    };
+
+    #[test]
+    fn replay_reinjects_inputs_at_the_same_instruction_count() {
+        let program = assemble("main:\n add r0, [0x30]\n jmp main");
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x40));
+        vm.start_recording();
+        vm.run(3);
+        vm.inject(ExternalInput::Memory {
+            address: 0x30,
+            value: 2,
+        })
+        .unwrap();
+        vm.run(5);
+        vm.inject(ExternalInput::Register {
+            register: Register::GeneralPurpose1,
+            value: 7,
+        })
+        .unwrap();
+        vm.run(4);
+        let recording = vm.stop_recording().unwrap();
+        let recording = InputRecording::from_bytes(&recording.to_bytes()).unwrap();
+        assert_eq!(recording.events.len(), 2);
+
+        let mut replayed = Vm::new(Vec::new(), VmConfig::new());
+        replayed.replay(&recording).unwrap();
+        replayed
+            .inject(ExternalInput::Memory {
+                address: 0x30,
+                value: 100,
+            })
+            .unwrap();
+        assert_eq!(replayed.run(12), (StopReason::BudgetExhausted, 12));
+        assert!(!replayed.is_replaying());
+        assert_eq!(replayed.instruction_count(), vm.instruction_count());
+        assert_eq!(replayed.cycle_count(), vm.cycle_count());
+        assert_eq!(replayed.snapshot(), vm.snapshot());
+    }
+
+    #[test]
+    fn replay_matches_with_interrupts_and_regions() {
+        // line 0 adds the input at 0x38 to r1
+        let program = assemble(
+            "main:\n\
+             mov [0x100], 1\n\
+             mov [0x120], handler\n\
+             .wait:\n\
+             hlt\n\
+             jmp .wait\n\
+             handler:\n\
+             add r1, [0x38]\n\
+             mov [0x110], 0",
+        );
+        let code = 0..program.len() as u32;
+        let new_vm = |program| {
+            let mut vm = Vm::new(program, VmConfig::new().memory_size(0x40));
+            vm.map_interrupt_controller(0x100);
+            vm.add_region(code.clone(), Permissions::READ_ONLY);
+            vm.add_region(0x38..0x3C, Permissions::READ_ONLY);
+            vm
+        };
+        let mut vm = new_vm(program);
+        assert_eq!(vm.run(10).0, StopReason::Halted);
+        vm.start_recording();
+        for value in [5, 7] {
+            let input = ExternalInput::Memory {
+                address: 0x38,
+                value,
+            };
+            vm.inject(input).unwrap();
+            vm.inject(ExternalInput::Interrupt { line: 0 }).unwrap();
+            assert_eq!(vm.run(10).0, StopReason::Halted);
+        }
+        assert_eq!(vm.register_value(&Register::GeneralPurpose1), 12);
+        let recording = vm.stop_recording().unwrap();
+        let recording = InputRecording::from_bytes(&recording.to_bytes()).unwrap();
+
+        let mut replayed = new_vm(Vec::new());
+        replayed.replay(&recording).unwrap();
+        for _ in 0..2 {
+            assert_eq!(replayed.run(10).0, StopReason::Halted);
+        }
+        assert_eq!(replayed.instruction_count(), vm.instruction_count());
+        assert_eq!(replayed.cycle_count(), vm.cycle_count());
+        assert_eq!(replayed.snapshot(), vm.snapshot());
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{
+    arch::Word,
+    error::VmError,
+    observer::VmObserver,
+    vm::{Register, StepOutcome, Vm},
+    watchpoint::WatchpointHit,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    Halted,
+    BudgetExhausted,
+    Breakpoint(Word),
+    Watchpoint(WatchpointHit),
+    /// the [`Vm::run_until`] predicate held with the pc at the given address
+    Condition(Word),
+    Fault(VmError),
+    PcOutOfMemory,
+}
+
+/// counts the step into `executed`, returning why execution should stop if it should
+fn stop_reason(outcome: Result<StepOutcome, VmError>, executed: &mut u64) -> Option<StopReason> {
+    match outcome {
+        Ok(StepOutcome::Executed | StepOutcome::Jumped { .. }) => {
+            *executed += 1;
+            None
+        }
+        Ok(StepOutcome::Halted) => {
+            *executed += 1;
+            Some(StopReason::Halted)
+        }
+        Ok(StepOutcome::WaitingForInterrupt) => Some(StopReason::Halted),
+        Ok(StepOutcome::Breakpoint(address)) => Some(StopReason::Breakpoint(address)),
+        Ok(StepOutcome::Watchpoint(hit)) => {
+            *executed += 1;
+            Some(StopReason::Watchpoint(hit))
+        }
+        Err(VmError::OutOfInstructions { .. }) => Some(StopReason::PcOutOfMemory),
+        Err(err) => Some(StopReason::Fault(err)),
+    }
+}
+
+impl<O: VmObserver> Vm<O> {
+    /// runs at most `limit` instructions, returning why execution stopped
+    /// and the amount of instructions executed
+    pub fn run(&mut self, limit: u64) -> (StopReason, u64) {
+        self.run_with(Some(limit), |_| false)
+    }
+
+    /// runs until `predicate` holds before an instruction is executed,
+    /// returning why execution stopped and the amount of instructions executed
+    pub fn run_until<P: FnMut(&Vm<O>) -> bool>(&mut self, predicate: P) -> (StopReason, u64) {
+        self.run_with(None, predicate)
+    }
+
+    fn run_with<P: FnMut(&Vm<O>) -> bool>(
+        &mut self,
+        limit: Option<u64>,
+        mut predicate: P,
+    ) -> (StopReason, u64) {
+        let mut executed = 0;
+        loop {
+            let pc = self.register_value(&Register::ProgramCounter);
+            let Some(block) = self.block_at(pc) else {
+                if let Some(reason) = self.limit_reached(limit, executed, &mut predicate) {
+                    return (reason, executed);
+                }
+                let outcome = self.run_next_instruction();
+                if let Some(reason) = stop_reason(outcome, &mut executed) {
+                    return (reason, executed);
+                }
+                continue;
+            };
+            for index in 0.. {
+                let Some(op) = self.block_op(block, index) else {
+                    break;
+                };
+                if self.register_value(&Register::ProgramCounter) != op.location
+                    || self.interrupts.is_deliverable()
+                {
+                    break;
+                }
+                if let Some(reason) = self.limit_reached(limit, executed, &mut predicate) {
+                    return (reason, executed);
+                }
+                let outcome = self.run_block_op(&op, index);
+                if let Some(reason) = stop_reason(outcome, &mut executed) {
+                    return (reason, executed);
+                }
+            }
+        }
+    }
+
+    fn limit_reached<P: FnMut(&Vm<O>) -> bool>(
+        &self,
+        limit: Option<u64>,
+        executed: u64,
+        predicate: &mut P,
+    ) -> Option<StopReason> {
+        if limit.is_some_and(|limit| executed >= limit) {
+            return Some(StopReason::BudgetExhausted);
+        }
+        if predicate(self) {
+            let address = self.register_value(&Register::ProgramCounter);
+            return Some(StopReason::Condition(address));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::StopReason;
+    use crate::{test_util::assemble, Register, Vm, VmConfig, VmError};
+
+    #[test]
+    fn run_until_stops_when_the_condition_holds() {
+        let program = assemble("mov r0, 3\nloop:\nsub r0, 1\njnz loop, r0\nhlt");
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x20));
+        let (reason, executed) =
+            vm.run_until(|vm| vm.register_value(&Register::GeneralPurpose0) == 1);
+        assert_eq!((reason, executed), (StopReason::Condition(0x0C), 4));
+        assert_eq!(vm.run_until(|_| false), (StopReason::Halted, 4));
+    }
+
+    #[test]
+    fn pc_out_of_memory_and_truncated_instructions() {
+        // jmp 0x20
+        let mut vm = Vm::new(
+            vec![0x11, 0x40, 0x00, 0x00, 0x00, 0x20],
+            VmConfig::new().memory_size(0x20),
+        );
+        assert_eq!(vm.run(10), (StopReason::PcOutOfMemory, 1));
+
+        // nop; jmp with its target cut off by the end of memory
+        let mut vm = Vm::new(vec![0x00, 0x11, 0x40, 0x00], VmConfig::new().memory_size(4));
+        let fault = VmError::TruncatedInstruction { pc: 1, len: 3 };
+        assert_eq!(vm.run(10), (StopReason::Fault(fault), 1));
+    }
+}
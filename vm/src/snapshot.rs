@@ -0,0 +1,422 @@
+use std::{fmt::Display, ops::Range};
+
+use crate::{
+    arch::Word,
+    interrupt::InterruptController,
+    memory::Memory,
+    observer::VmObserver,
+    vm::{Register, Vm},
+};
+
+const MAGIC: &[u8; 4] = b"VC2S";
+pub const SNAPSHOT_VERSION: u32 = 1;
+/// addresses are [`Word`]s, so more memory can't be reached
+const MAX_MEMORY_SIZE: u64 = 1 << Word::BITS;
+/// zero runs shorter than a chunk header are stored inline instead of starting a new chunk
+const CHUNK_HEADER_LENGTH: usize = 8;
+
+/// the state of a machine: registers, halt state, interrupt controller, devices and memory
+///
+/// encoded as big endian, in order:
+/// - `VC2S` and the format version (`u32`)
+/// - `r0`, `r1`, `fl`, `pc` and the stack pointer (`u32` each)
+/// - the halt state, `0` when running or `1` followed by the location after the `hlt` (`u32`)
+/// - the interrupt controller
+/// - the amount of devices (`u32`) and every device as the start and end of its range (`u32` each),
+///   the length of its state (`u32`) and the state
+/// - the memory size (`u64`), at most 4 GiB, and the amount of memory chunks (`u32`)
+/// - every chunk as its address (`u32`), length (`u32`) and bytes,
+///   memory outside of the chunks is zeroed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    registers: [Word; 4],
+    stack_pointer: Word,
+    hlt_location: Option<Word>,
+    interrupts: InterruptController,
+    /// the saved state of every mapped device and the range it's mapped at
+    devices: Vec<(Range<Word>, Vec<u8>)>,
+    memory_size: usize,
+    chunks: Vec<(Word, Vec<u8>)>,
+}
+
+/// errors reading snapshots and input recordings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    InvalidHaltState(u8),
+    /// the interrupt controller's base or handling state isn't `0` or `1`
+    InvalidInterruptState(u8),
+    /// unknown kind of recorded input
+    InvalidInput(u8),
+    /// the snapshot ends before `len` more bytes could be read
+    UnexpectedEnd {
+        len: usize,
+    },
+    /// the memory is larger than the 4 GiB addresses can reach
+    MemoryTooLarge(u64),
+    ChunkOutOfBounds {
+        address: Word,
+        len: usize,
+        memory_size: usize,
+    },
+    UnsupportedArchitecture,
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::InvalidMagic => write!(f, "not a vc2 snapshot or recording"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {version}")
+            }
+            SnapshotError::InvalidHaltState(state) => {
+                write!(f, "invalid halt state '{state:#04X}'")
+            }
+            SnapshotError::InvalidInterruptState(state) => {
+                write!(f, "invalid interrupt controller state '{state:#04X}'")
+            }
+            SnapshotError::InvalidInput(kind) => {
+                write!(f, "invalid recorded input '{kind:#04X}'")
+            }
+            SnapshotError::UnexpectedEnd { len } => {
+                write!(f, "snapshot ended while reading {len} bytes")
+            }
+            SnapshotError::MemoryTooLarge(memory_size) => {
+                write!(f, "{memory_size} bytes of memory can't be addressed")
+            }
+            SnapshotError::ChunkOutOfBounds {
+                address,
+                len,
+                memory_size,
+            } => write!(
+                f,
+                "memory chunk of {len} bytes at {address:#010X} is outside of {memory_size} bytes of memory"
+            ),
+            SnapshotError::UnsupportedArchitecture => {
+                write!(f, "snapshot memory doesn't fit in this architecture")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() < len {
+            return Err(SnapshotError::UnexpectedEnd { len });
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+    pub(crate) fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+    pub(crate) fn u32(&mut self) -> Result<u32, SnapshotError> {
+        let bytes = self.take(4)?.try_into().expect("took 4 bytes");
+        Ok(u32::from_be_bytes(bytes))
+    }
+    pub(crate) fn u64(&mut self) -> Result<u64, SnapshotError> {
+        let bytes = self.take(8)?.try_into().expect("took 8 bytes");
+        Ok(u64::from_be_bytes(bytes))
+    }
+}
+
+impl Snapshot {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&SNAPSHOT_VERSION.to_be_bytes());
+        for register in self.registers {
+            bytes.extend_from_slice(&register.to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.stack_pointer.to_be_bytes());
+        match self.hlt_location {
+            Some(location) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&location.to_be_bytes());
+            }
+            None => bytes.push(0),
+        }
+        self.interrupts.write_to(&mut bytes);
+        bytes.extend_from_slice(&(self.devices.len() as u32).to_be_bytes());
+        for (range, state) in &self.devices {
+            bytes.extend_from_slice(&range.start.to_be_bytes());
+            bytes.extend_from_slice(&range.end.to_be_bytes());
+            bytes.extend_from_slice(&(state.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(state);
+        }
+        bytes.extend_from_slice(&(self.memory_size as u64).to_be_bytes());
+        bytes.extend_from_slice(&(self.chunks.len() as u32).to_be_bytes());
+        for (address, chunk) in &self.chunks {
+            bytes.extend_from_slice(&address.to_be_bytes());
+            bytes.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(chunk);
+        }
+        bytes
+    }
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+        let version = reader.u32()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let registers = [reader.u32()?, reader.u32()?, reader.u32()?, reader.u32()?];
+        let stack_pointer = reader.u32()?;
+        let hlt_location = match reader.u8()? {
+            0 => None,
+            1 => Some(reader.u32()?),
+            state => return Err(SnapshotError::InvalidHaltState(state)),
+        };
+        let interrupts = InterruptController::read(&mut reader)?;
+        let device_count = reader.u32()?;
+        let mut devices = Vec::new();
+        for _ in 0..device_count {
+            let range = reader.u32()?..reader.u32()?;
+            let len = reader.u32()? as usize;
+            devices.push((range, reader.take(len)?.to_vec()));
+        }
+        let memory_size = checked_memory_size(reader.u64()?)?;
+        let chunk_count = reader.u32()?;
+        let mut chunks = Vec::new();
+        for _ in 0..chunk_count {
+            let address = reader.u32()?;
+            let len = reader.u32()? as usize;
+            check_chunk(address, len, memory_size)?;
+            chunks.push((address, reader.take(len)?.to_vec()));
+        }
+        Ok(Self {
+            registers,
+            stack_pointer,
+            hlt_location,
+            interrupts,
+            devices,
+            memory_size,
+            chunks,
+        })
+    }
+}
+
+fn checked_memory_size(memory_size: u64) -> Result<usize, SnapshotError> {
+    if memory_size > MAX_MEMORY_SIZE {
+        return Err(SnapshotError::MemoryTooLarge(memory_size));
+    }
+    memory_size
+        .try_into()
+        .map_err(|_| SnapshotError::UnsupportedArchitecture)
+}
+
+fn check_chunk(address: Word, len: usize, memory_size: usize) -> Result<(), SnapshotError> {
+    if address as usize + len > memory_size {
+        return Err(SnapshotError::ChunkOutOfBounds {
+            address,
+            len,
+            memory_size,
+        });
+    }
+    Ok(())
+}
+
+/// splits the regions of memory into the chunks containing every non-zero byte
+fn chunks<'a>(regions: impl Iterator<Item = (usize, &'a [u8])>) -> Vec<(Word, Vec<u8>)> {
+    let mut chunks: Vec<(Word, Vec<u8>)> = Vec::new();
+    for (region_address, memory) in regions {
+        let mut offset = 0;
+        while let Some(start) = memory[offset..].iter().position(|byte| *byte != 0) {
+            let start = offset + start;
+            let end = memory[start..]
+                .iter()
+                .position(|byte| *byte == 0)
+                .map_or(memory.len(), |len| start + len);
+            let address = region_address + start;
+            match chunks.last_mut() {
+                Some((chunk_address, chunk))
+                    if address - (*chunk_address as usize + chunk.len()) < CHUNK_HEADER_LENGTH =>
+                {
+                    // everything between chunks is zero
+                    chunk.resize(address - *chunk_address as usize, 0);
+                    chunk.extend_from_slice(&memory[start..end]);
+                }
+                _ => chunks.push((address as Word, memory[start..end].to_vec())),
+            }
+            offset = end;
+        }
+    }
+    chunks
+}
+
+impl<O: VmObserver> Vm<O> {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            registers: [
+                Register::GeneralPurpose0,
+                Register::GeneralPurpose1,
+                Register::Flag,
+                Register::ProgramCounter,
+            ]
+            .map(|register| self.register_value(&register)),
+            stack_pointer: self.registers.stack_pointer,
+            hlt_location: self.hlt_location,
+            interrupts: self.interrupts,
+            devices: self
+                .devices
+                .values()
+                .map(|mapped| (mapped.range.clone(), mapped.device.save_state()))
+                .collect(),
+            memory_size: self.memory.len(),
+            chunks: chunks(self.memory.regions()),
+        }
+    }
+    /// replaces registers, halt state, the interrupt controller and memory with the ones in `snapshot`,
+    /// devices mapped at the same range as in `snapshot` get their state back,
+    /// breakpoints, watchpoints, the observer, the config apart from the memory size
+    /// and the execution engine are kept
+    /// while the history of executed instructions is cleared
+    ///
+    /// # Errors
+    /// if the memory of `snapshot` can't be addressed or a device rejects its state,
+    /// only devices before it are restored then
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        checked_memory_size(snapshot.memory_size as u64)?;
+        for (address, chunk) in &snapshot.chunks {
+            check_chunk(*address, chunk.len(), snapshot.memory_size)?;
+        }
+        for (range, state) in &snapshot.devices {
+            let mapped = self
+                .devices
+                .values_mut()
+                .rev()
+                .find(|mapped| mapped.range == *range);
+            if let Some(mapped) = mapped {
+                mapped.device.restore_state(state)?;
+            }
+        }
+        let mut memory = Memory::new(self.config.memory_backend, snapshot.memory_size);
+        for (address, chunk) in &snapshot.chunks {
+            memory.write(*address as usize, chunk);
+        }
+        self.memory = memory;
+        self.config.memory_size = snapshot.memory_size;
+        let [r0, r1, fl, pc] = snapshot.registers;
+        self.registers.general_purpose_0 = r0;
+        self.registers.general_purpose_1 = r1;
+        self.registers.flag = fl;
+        self.registers.program_counter = pc;
+        self.registers.stack_pointer = snapshot.stack_pointer;
+        self.hlt_location = snapshot.hlt_location;
+        self.interrupts = snapshot.interrupts;
+        self.resumed_breakpoint = None;
+        self.watchpoint_hit = None;
+        self.clear_history();
+        self.instruction_cache.reset(snapshot.memory_size);
+        let engine = self.execution_engine();
+        self.set_execution_engine(engine);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        test_util::assemble, MemoryBackend, Register, Snapshot, SnapshotError, StopReason, Vm,
+        VmConfig,
+    };
+
+    #[test]
+    fn restored_vm_continues_identically() {
+        let program = assemble(
+            "main:\n mov r0, 3\n .loop:\n sub r0, 1\n mov [0x30], r0\n jnz .loop, r0\n hlt",
+        );
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x1000));
+        vm.run(5);
+
+        let snapshot = vm.snapshot();
+        let bytes = snapshot.to_bytes();
+        assert!(bytes.len() < 0x100);
+        assert_eq!(Snapshot::from_bytes(&bytes), Ok(snapshot.clone()));
+
+        let mut restored = Vm::new(Vec::new(), VmConfig::new());
+        restored.restore(&snapshot).unwrap();
+        assert_eq!(vm.run(100), restored.run(100));
+        assert_eq!(restored.register_value(&Register::GeneralPurpose0), 0);
+        assert_eq!(restored.memory_value(&0x30), Ok(0));
+        assert_eq!(restored.run(1).0, StopReason::Halted);
+    }
+
+    #[test]
+    fn paged_snapshot_contains_touched_pages() {
+        let paged = VmConfig::new().memory_backend(MemoryBackend::Paged);
+        let program = assemble("mov [0xFFFF0000], 7\nhlt");
+        let mut vm = Vm::new(program, paged.memory_size(0xFFFF_FFFF));
+        vm.run(10);
+
+        let bytes = vm.snapshot().to_bytes();
+        assert!(bytes.len() < 0x100);
+        let mut restored = Vm::new(Vec::new(), paged);
+        restored
+            .restore(&Snapshot::from_bytes(&bytes).unwrap())
+            .unwrap();
+        assert_eq!(restored.memory_value(&0xFFFF0000), Ok(7));
+        assert_eq!(restored.memory_value(&0x8000_0000), Ok(0));
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        assert_eq!(
+            Snapshot::from_bytes(b"VC2X"),
+            Err(SnapshotError::InvalidMagic)
+        );
+        let mut bytes = Vm::new(Vec::new(), VmConfig::new().memory_size(0x10))
+            .snapshot()
+            .to_bytes();
+        bytes[7] = 2;
+        assert_eq!(
+            Snapshot::from_bytes(&bytes),
+            Err(SnapshotError::UnsupportedVersion(2))
+        );
+        bytes[7] = 1;
+        bytes.pop();
+        assert_eq!(
+            Snapshot::from_bytes(&bytes),
+            Err(SnapshotError::UnexpectedEnd { len: 4 })
+        );
+    }
+
+    #[test]
+    fn memory_past_the_address_space_is_rejected() {
+        let mut bytes = Vm::new(Vec::new(), VmConfig::new().memory_size(0x10))
+            .snapshot()
+            .to_bytes();
+        // the memory size follows the interrupt controller and is followed by the chunk count
+        let memory_size = bytes.len() - 12;
+        bytes[memory_size..memory_size + 8].copy_from_slice(&(1u64 << 40).to_be_bytes());
+        assert_eq!(
+            Snapshot::from_bytes(&bytes),
+            Err(SnapshotError::MemoryTooLarge(1 << 40))
+        );
+
+        bytes[memory_size..memory_size + 8].copy_from_slice(&0x10u64.to_be_bytes());
+        bytes.pop();
+        bytes.extend_from_slice(&[1, 0, 0, 0, 0x0E, 0, 0, 0, 4, 1, 2, 3, 4]);
+        assert_eq!(
+            Snapshot::from_bytes(&bytes),
+            Err(SnapshotError::ChunkOutOfBounds {
+                address: 0x0E,
+                len: 4,
+                memory_size: 0x10
+            })
+        );
+    }
+}
@@ -0,0 +1,10 @@
+use vc2_assembler::{instructions::InstructionOrConstant, Assembler, Parser};
+
+pub(crate) fn assemble(source: &str) -> Vec<u8> {
+    let nodes = Parser::new(source.as_bytes())
+        .parse()
+        .into_iter()
+        .collect::<Result<Vec<InstructionOrConstant>, _>>()
+        .expect("valid source");
+    Assembler::new(&nodes).assemble()
+}
@@ -1,21 +1,61 @@
+use std::collections::BTreeMap;
+
 use crate::{
-    arch::Word,
-    named_instruction::{self, NamedInstruction},
+    alu::{self, AluResult},
+    arch::{stack_top, Word, WORD_SIZE},
+    block::BlockCache,
+    breakpoint::Breakpoint,
+    cache::InstructionCache,
+    config::VmConfig,
+    decode::{decode, DecodeError, MAX_INSTRUCTION_LENGTH},
+    device::MappedDevice,
+    error::VmError,
+    history::History,
+    interrupt::InterruptController,
+    memory::Memory,
+    observer::VmObserver,
+    region::{AccessKind, Region},
+    replay::{InputRecording, Replay},
+    watchpoint::{Access, Watchpoint, WatchpointHit},
 };
 
 pub type Immediate = crate::arch::Word;
 
-pub struct Vm {
-    memory: Vec<u8>,
-    registers: VmRegisters,
-    hlt_location: Option<Word>,
+pub struct Vm<O: VmObserver = ()> {
+    pub(crate) memory: Memory,
+    pub(crate) registers: VmRegisters,
+    pub(crate) hlt_location: Option<Word>,
+    pub(crate) breakpoints: BTreeMap<Word, Breakpoint>,
+    pub(crate) resumed_breakpoint: Option<Word>,
+    pub(crate) watchpoints: BTreeMap<usize, Watchpoint>,
+    pub(crate) next_watchpoint_id: usize,
+    pub(crate) watchpoint_hit: Option<WatchpointHit>,
+    /// location of the instruction currently being executed
+    pub(crate) instruction_location: Word,
+    pub(crate) instruction_cache: InstructionCache,
+    /// only present when the basic block engine is used
+    pub(crate) block_cache: Option<BlockCache>,
+    pub(crate) history: History,
+    pub(crate) instruction_count: u64,
+    pub(crate) cycle_count: u64,
+    pub(crate) recording: Option<InputRecording>,
+    pub(crate) replay: Option<Replay>,
+    pub(crate) devices: BTreeMap<usize, MappedDevice>,
+    pub(crate) next_device_id: usize,
+    pub(crate) regions: BTreeMap<usize, Region>,
+    pub(crate) next_region_id: usize,
+    pub(crate) interrupts: InterruptController,
+    pub(crate) config: VmConfig,
+    pub(crate) observer: O,
 }
 
 pub struct VmRegisters {
-    general_purpose_0: Word,
-    general_purpose_1: Word,
-    flag: Word,
-    program_counter: Word,
+    pub(crate) general_purpose_0: Word,
+    pub(crate) general_purpose_1: Word,
+    pub(crate) flag: Word,
+    pub(crate) program_counter: Word,
+    /// address of the word pushed last, only used by the stack profile
+    pub(crate) stack_pointer: Word,
 }
 
 pub enum Flag {
@@ -27,18 +67,21 @@ pub enum Flag {
 }
 
 impl Flag {
-    pub fn is_active(&self, value: u32) -> bool {
+    pub fn bit(&self) -> Word {
         match self {
-            Flag::Overflow => (value & 0b0000_0001) != 0,
-            Flag::CarryOrBorrow => (value & 0b0000_0010) >> 1 != 0,
-            Flag::Equal => (value & 0b0000_0100) >> 2 != 0,
-            Flag::Less => (value & 0b0000_1000) >> 3 != 0,
-            Flag::Below => (value & 0b0001_0000) >> 4 != 0,
+            Flag::Overflow => 0b0000_0001,
+            Flag::CarryOrBorrow => 0b0000_0010,
+            Flag::Equal => 0b0000_0100,
+            Flag::Less => 0b0000_1000,
+            Flag::Below => 0b0001_0000,
         }
     }
+    pub fn is_active(&self, value: u32) -> bool {
+        value & self.bit() != 0
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Register {
     GeneralPurpose0,
     GeneralPurpose1,
@@ -47,7 +90,8 @@ pub enum Register {
 }
 
 impl TryFrom<u8> for Register {
-    type Error = String;
+    /// the invalid value
+    type Error = u8;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
@@ -55,12 +99,12 @@ impl TryFrom<u8> for Register {
             0b01 => Ok(Self::GeneralPurpose1),
             0b10 => Ok(Self::Flag),
             0b11 => Ok(Self::ProgramCounter),
-            value => Err(format!("invalid register 0b{value:b}")),
+            value => Err(value),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Selector {
     Register,
@@ -70,7 +114,8 @@ pub enum Selector {
 }
 
 impl TryFrom<u8> for Selector {
-    type Error = String;
+    /// the invalid value
+    type Error = u8;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
@@ -78,12 +123,12 @@ impl TryFrom<u8> for Selector {
             0b01 => Ok(Self::Immediate),
             0b10 => Ok(Self::RegisterAddress),
             0b11 => Ok(Self::ImmediateAddress),
-            value => Err(format!("invalid selector 0b{value:b}")),
+            value => Err(value),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Config {
     RegisterFromRegister(Register, Register),
     RegisterFromImmediate(Register, Immediate),
@@ -97,7 +142,20 @@ pub enum Config {
     ImmediateFromRegister(Immediate, Register),
 }
 
-#[derive(Debug)]
+impl Config {
+    /// the register the result is written to
+    pub fn destination_register(&self) -> Option<Register> {
+        match self {
+            Config::RegisterFromRegister(register, _)
+            | Config::RegisterFromImmediate(register, _)
+            | Config::RegisterFromRegisterAddress(register, _)
+            | Config::RegisterFromImmediateAddress(register, _) => Some(*register),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum JmpConfig {
     Register(Register),
     Immediate(Immediate),
@@ -105,7 +163,7 @@ pub enum JmpConfig {
     ImmediateAddress(Immediate),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConditionalJmpConfig {
     RegisterFromRegister(Register, Register),
     RegisterFromImmediate(Register, Immediate),
@@ -121,14 +179,40 @@ pub enum ConditionalJmpConfig {
     ImmediateAddressFromImmediate(Immediate, Immediate),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NotConfig {
     Register(Register),
     RegisterAddress(Register),
     ImmediateAddress(Immediate),
 }
 
-#[derive(Debug)]
+/// the value `push` puts on the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushConfig {
+    Register(Register),
+    Immediate(Immediate),
+    RegisterAddress(Register),
+    ImmediateAddress(Immediate),
+}
+
+/// where `pop` writes the value it takes off the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PopConfig {
+    Register(Register),
+    RegisterAddress(Register),
+    ImmediateAddress(Immediate),
+}
+
+/// the location `call` jumps to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallConfig {
+    Register(Register),
+    Immediate(Immediate),
+    RegisterAddress(Register),
+    ImmediateAddress(Immediate),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
     Nop,
     Hlt,
@@ -147,11 +231,16 @@ pub enum Instruction {
     IDiv(Config),
     Rem(Config),
     Cmp(Config),
-    Jmp(JmpConfig),
-    Jz(ConditionalJmpConfig),
-    Jnz(ConditionalJmpConfig),
+    Jmp(JmpConfig, JmpVariant),
+    Jz(ConditionalJmpConfig, JmpVariant),
+    Jnz(ConditionalJmpConfig, JmpVariant),
+    Push(PushConfig),
+    Pop(PopConfig),
+    Call(CallConfig),
+    Ret,
 }
 
+#[derive(Debug, Clone, Copy)]
 pub enum MathOpVariant {
     Or,
     And,
@@ -165,351 +254,138 @@ pub enum MathOpVariant {
     Rem,
 }
 
-pub fn invalid_architecture_message<E>(_error: E) -> String {
-    String::from("architecture should support 32 bit word pointers")
+fn unsupported_architecture<E>(_error: E) -> VmError {
+    VmError::UnsupportedArchitecture
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum JmpVariant {
     Absolute,
+    /// the target is an offset from the start of the jump instruction
     Relative,
 }
 
+impl JmpVariant {
+    pub(crate) fn destination(&self, instruction_location: Word, target: Word) -> Word {
+        match self {
+            JmpVariant::Absolute => target,
+            JmpVariant::Relative => instruction_location.wrapping_add(target),
+        }
+    }
+}
+
 pub enum ConditionalJmpVariant {
     Jz,
     Jnz,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    Executed,
+    Jumped { from: Word, to: Word },
+    Halted,
+    WaitingForInterrupt,
+    Breakpoint(Word),
+    Watchpoint(WatchpointHit),
+}
+
 impl Vm {
-    pub fn new(instructions: Vec<u8>, memory_size: usize) -> Self {
-        let mut memory = vec![0; memory_size];
-        instructions
-            .into_iter()
-            .enumerate()
-            .for_each(|(idx, byte)| memory[idx] = byte);
+    /// loads `instructions` at the start of memory
+    pub fn new(instructions: Vec<u8>, config: VmConfig) -> Self {
+        let mut memory = Memory::new(config.memory_backend, config.memory_size);
+        assert!(
+            memory.write(0, &instructions),
+            "{} bytes of instructions don't fit in {} bytes of memory",
+            instructions.len(),
+            config.memory_size
+        );
         Self {
             memory,
+            config,
             hlt_location: None,
+            breakpoints: BTreeMap::new(),
+            resumed_breakpoint: None,
+            watchpoints: BTreeMap::new(),
+            next_watchpoint_id: 0,
+            watchpoint_hit: None,
+            instruction_location: 0,
+            instruction_cache: InstructionCache::new(config.memory_size),
+            block_cache: None,
+            history: History::default(),
+            instruction_count: 0,
+            cycle_count: 0,
+            recording: None,
+            replay: None,
+            devices: BTreeMap::new(),
+            next_device_id: 0,
+            regions: BTreeMap::new(),
+            next_region_id: 0,
+            interrupts: InterruptController::default(),
+            observer: (),
             registers: VmRegisters {
                 general_purpose_0: 0,
                 general_purpose_1: 0,
                 flag: 0,
                 program_counter: 0,
+                stack_pointer: stack_top(config.memory_size),
             },
         }
     }
-    fn current_byte(&self) -> Result<u8, String> {
-        self.registers
-            .program_counter
-            .try_into()
-            .map_err(invalid_architecture_message)
-            .map(|idx: usize| {
-                self.memory.get(idx).ok_or_else(|| {
-                    format!(
-                        "cannot get current byte: index {idx} > {}",
-                        self.memory.len()
-                    )
-                })
-            })?
-            .copied()
-    }
-    fn step(&mut self) {
-        self.registers.program_counter += 1;
-    }
-    fn parse_conditional_jmp_target(
-        &mut self,
-        destination_selector: Selector,
-        source_selector: Selector,
-        destination: Result<Register, String>,
-        source: Result<Register, String>,
-    ) -> Result<ConditionalJmpConfig, String> {
-        let config = match (destination_selector, source_selector) {
-            (Selector::Register, Selector::Register) => {
-                ConditionalJmpConfig::RegisterFromRegister(destination?, source?)
-            }
-            (Selector::Register, Selector::Immediate) => {
-                ConditionalJmpConfig::RegisterFromImmediate(destination?, self.consume_immediate()?)
-            }
-            (Selector::Register, Selector::RegisterAddress) => {
-                ConditionalJmpConfig::RegisterFromRegisterAddress(destination?, source?)
-            }
-            (Selector::Register, Selector::ImmediateAddress) => {
-                ConditionalJmpConfig::RegisterFromImmediateAddress(
-                    destination?,
-                    self.consume_immediate()?,
-                )
-            }
-            (Selector::RegisterAddress, Selector::Register) => {
-                ConditionalJmpConfig::RegisterAddressFromRegister(destination?, source?)
-            }
-            (Selector::RegisterAddress, Selector::Immediate) => {
-                ConditionalJmpConfig::RegisterAddressFromImmediate(
-                    destination?,
-                    self.consume_immediate()?,
-                )
-            }
-            (Selector::ImmediateAddress, Selector::Register) => {
-                ConditionalJmpConfig::ImmediateAddressFromRegister(
-                    self.consume_immediate()?,
-                    source?,
-                )
-            }
-            (Selector::ImmediateAddress, Selector::Immediate) => {
-                ConditionalJmpConfig::ImmediateAddressFromImmediate(
-                    self.consume_immediate()?,
-                    self.consume_immediate()?,
-                )
-            }
-            (Selector::Immediate, Selector::Immediate) => {
-                ConditionalJmpConfig::ImmediateFromImmediate(
-                    self.consume_immediate()?,
-                    self.consume_immediate()?,
-                )
-            }
-            (Selector::Immediate, Selector::Register) => {
-                ConditionalJmpConfig::ImmediateFromRegister(self.consume_immediate()?, source?)
-            }
-            (Selector::Immediate, Selector::RegisterAddress) => {
-                ConditionalJmpConfig::ImmediateFromRegisterAddress(
-                    self.consume_immediate()?,
-                    source?,
-                )
-            }
-            (Selector::Immediate, Selector::ImmediateAddress) => {
-                ConditionalJmpConfig::ImmediateFromImmediateAddress(
-                    self.consume_immediate()?,
-                    self.consume_immediate()?,
-                )
-            }
-            variant => Err(format!(
-                "invalid selector/destination combo '{variant:?}' at {}",
-                self.registers.program_counter
-            ))?,
-        };
-
-        Ok(config)
-    }
-
-    fn parse_target(
-        &mut self,
-        destination_selector: Selector,
-        source_selector: Selector,
-        destination: Result<Register, String>,
-        source: Result<Register, String>,
-    ) -> Result<Config, String> {
-        let config = match (destination_selector, source_selector) {
-            (Selector::Register, Selector::Register) => {
-                Config::RegisterFromRegister(destination?, source?)
-            }
-            (Selector::Register, Selector::Immediate) => {
-                Config::RegisterFromImmediate(destination?, self.consume_immediate()?)
-            }
-            (Selector::Register, Selector::RegisterAddress) => {
-                Config::RegisterFromRegisterAddress(destination?, source?)
-            }
-            (Selector::Register, Selector::ImmediateAddress) => {
-                Config::RegisterFromImmediateAddress(destination?, self.consume_immediate()?)
-            }
-            (Selector::RegisterAddress, Selector::Register) => {
-                Config::RegisterAddressFromRegister(destination?, source?)
-            }
-            (Selector::RegisterAddress, Selector::Immediate) => {
-                Config::RegisterAddressFromImmediate(destination?, self.consume_immediate()?)
-            }
-            (Selector::ImmediateAddress, Selector::Register) => {
-                Config::ImmediateAddressFromRegister(self.consume_immediate()?, source?)
-            }
-            (Selector::ImmediateAddress, Selector::Immediate) => {
-                Config::ImmediateAddressFromImmediate(
-                    self.consume_immediate()?,
-                    self.consume_immediate()?,
-                )
-            }
-            (Selector::Immediate, Selector::Immediate) => {
-                Config::ImmediateFromImmediate(self.consume_immediate()?, self.consume_immediate()?)
-            }
-            (Selector::Immediate, Selector::Register) => {
-                Config::ImmediateFromRegister(self.consume_immediate()?, source?)
-            }
-            variant => Err(format!(
-                "invalid selector/destination combo '{variant:?}' at {}",
-                self.registers.program_counter
-            ))?,
-        };
-
-        Ok(config)
-    }
-
-    fn parse_mov(&mut self) -> Result<Instruction, String> {
-        self.step();
-        let input = self.current_byte()?;
-        self.step();
-
-        let destination_selector: Selector = ((input & 0b1100_0000) >> 6).try_into()?;
-        let source_selector: Selector = ((input & 0b0011_0000) >> 4).try_into()?;
-
-        let destination: Result<Register, _> = ((input & 0b0000_1100) >> 2).try_into();
-        let source: Result<Register, _> = (input & 0b0000_0011).try_into();
-
-        let instruction =
-            self.parse_target(destination_selector, source_selector, destination, source)?;
+}
 
-        Ok(Instruction::Mov(instruction))
-    }
-    fn parse_nop(&mut self) -> Result<Instruction, String> {
-        self.step();
-        Ok(Instruction::Nop)
+impl<O: VmObserver> Vm<O> {
+    /// attaches `observer`, replacing the current one
+    pub fn with_observer<N: VmObserver>(self, observer: N) -> Vm<N> {
+        Vm {
+            memory: self.memory,
+            registers: self.registers,
+            hlt_location: self.hlt_location,
+            breakpoints: self.breakpoints,
+            resumed_breakpoint: self.resumed_breakpoint,
+            watchpoints: self.watchpoints,
+            next_watchpoint_id: self.next_watchpoint_id,
+            watchpoint_hit: self.watchpoint_hit,
+            instruction_location: self.instruction_location,
+            instruction_cache: self.instruction_cache,
+            block_cache: self.block_cache,
+            history: self.history,
+            instruction_count: self.instruction_count,
+            cycle_count: self.cycle_count,
+            recording: self.recording,
+            replay: self.replay,
+            devices: self.devices,
+            next_device_id: self.next_device_id,
+            regions: self.regions,
+            next_region_id: self.next_region_id,
+            interrupts: self.interrupts,
+            config: self.config,
+            observer,
+        }
     }
-    fn parse_hlt(&mut self) -> Result<Instruction, String> {
-        self.step();
-        Ok(Instruction::Hlt)
+    pub fn observer(&self) -> &O {
+        &self.observer
     }
-    fn parse_not(&mut self) -> Result<Instruction, String> {
-        self.step();
-        let input = self.current_byte()?;
-        self.step();
-
-        let selector: Selector = ((input & 0b1100_0000) >> 6).try_into()?;
-        let destination: Result<Register, _> = ((input & 0b0000_1100) >> 2).try_into();
-
-        let config = match selector {
-            Selector::Register => NotConfig::Register(destination?),
-            Selector::Immediate => {
-                return Err("invalid selector 'immediate' for not instruction".to_string())
-            }
-            Selector::RegisterAddress => NotConfig::RegisterAddress(destination?),
-            Selector::ImmediateAddress => NotConfig::ImmediateAddress(self.consume_immediate()?),
-        };
-
-        Ok(Instruction::Not(config))
-    }
-    fn consume_immediate(&mut self) -> Result<u32, String> {
-        let byte_0 = self.current_byte()?;
-        self.step();
-        let byte_1 = self.current_byte()?;
-        self.step();
-        let byte_2 = self.current_byte()?;
-        self.step();
-        let byte_3 = self.current_byte()?;
-        self.step();
-        Ok(u32::from_be_bytes([byte_0, byte_1, byte_2, byte_3]))
-    }
-    fn parse_jmp(&mut self) -> Result<Instruction, String> {
-        self.step();
-        let input = self.current_byte()?;
-        self.step();
-
-        log::debug!("parsing input '{input:#08b}'");
-
-        let selector = ((input & 0b1100_0000) >> 6).try_into()?;
-        let destination = ((input & 0b0000_1100) >> 2).try_into();
-
-        let config = match selector {
-            Selector::Register => JmpConfig::Register(destination?),
-            Selector::Immediate => JmpConfig::Immediate(self.consume_immediate()?),
-            Selector::RegisterAddress => JmpConfig::RegisterAddress(destination?),
-            Selector::ImmediateAddress => JmpConfig::ImmediateAddress(self.consume_immediate()?),
-        };
-
-        Ok(Instruction::Jmp(config))
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
     }
-
-    fn parse_conditional_jmp(&mut self) -> Result<Instruction, String> {
-        let instruction: NamedInstruction = self.current_byte()?.try_into()?;
-        self.step();
-        let constructor = match instruction {
-            named_instruction::Jz => Instruction::Jz,
-            named_instruction::Jnz => Instruction::Jnz,
-            field => Err(format!("invalid instruction {field:?}"))?,
-        };
-
-        let input = self.current_byte()?;
-        self.step();
-
-        log::debug!("parsing target ({input:#08b})");
-
-        let destination_selector: Selector = ((input & 0b1100_0000) >> 6).try_into()?;
-        let source_selector: Selector = ((input & 0b0011_0000) >> 4).try_into()?;
-        let destination: Result<Register, _> = ((input & 0b0000_1100) >> 2).try_into();
-        let source: Result<Register, _> = (input & 0b0000_0011).try_into();
-
-        let config = self.parse_conditional_jmp_target(
-            destination_selector,
-            source_selector,
-            destination,
-            source,
-        )?;
-
-        Ok(constructor(config))
-    }
-
-    fn parse_math_op(&mut self) -> Result<Instruction, String> {
-        let instruction: NamedInstruction = self.current_byte()?.try_into()?;
-        self.step();
-        let constructor = match instruction {
-            named_instruction::Or => Instruction::Or,
-            named_instruction::And => Instruction::And,
-            named_instruction::Xor => Instruction::Xor,
-            named_instruction::Shl => Instruction::Shl,
-            named_instruction::Shr => Instruction::Shr,
-            named_instruction::Add => Instruction::Add,
-            named_instruction::Sub => Instruction::Sub,
-            named_instruction::Mul => Instruction::Mul,
-            named_instruction::IMul => Instruction::IMul,
-            named_instruction::Div => Instruction::Div,
-            named_instruction::IDiv => Instruction::IDiv,
-            named_instruction::Rem => Instruction::Rem,
-            named_instruction::Cmp => Instruction::Cmp,
-            instruction => unreachable!(
-                "invalid instruction {instruction:?} at {:?}",
-                self.registers.program_counter
-            ),
-        };
-
-        let input = self.current_byte()?;
-        self.step();
-
-        let destination_selector = (input & 0b1100_0000) >> 6;
-        let destination_selector: Selector = destination_selector.try_into()?;
-        let source_selector = (input & 0b0011_0000) >> 4;
-        let source_selector: Selector = source_selector.try_into()?;
-        let destination = (input & 0b0000_1100) >> 2;
-        let destination = destination.try_into();
-        let source = input & 0b0000_0011;
-        let source = source.try_into();
-
-        let config =
-            self.parse_target(destination_selector, source_selector, destination, source)?;
-
-        Ok(constructor(config))
-    }
-    fn parse_next_instruction(&mut self) -> Result<Instruction, String> {
-        let current_byte = self.current_byte()?;
-        log::debug!("current byte: {current_byte:#02X}");
-        let next: NamedInstruction = current_byte.try_into()?;
-        log::debug!("parsing instruction {next:?} ({current_byte:#02X})");
-        match next {
-            named_instruction::Nop => self.parse_nop(),
-            named_instruction::Hlt => self.parse_hlt(),
-            named_instruction::Mov => self.parse_mov(),
-
-            named_instruction::Or
-            | named_instruction::And
-            | named_instruction::Xor
-            | named_instruction::Shl
-            | named_instruction::Shr
-            | named_instruction::Add
-            | named_instruction::Sub
-            | named_instruction::Mul
-            | named_instruction::IMul
-            | named_instruction::Div
-            | named_instruction::IDiv
-            | named_instruction::Rem
-            | named_instruction::Cmp => self.parse_math_op(),
-            named_instruction::Not => self.parse_not(),
-            named_instruction::Jmp => self.parse_jmp(),
-            named_instruction::Jz | named_instruction::Jnz => self.parse_conditional_jmp(),
+    /// decodes the instruction at `address` without executing it
+    pub fn decode_at(&self, address: Word) -> Result<(Instruction, usize), DecodeError> {
+        let start = address as usize;
+        let len = self
+            .memory
+            .len()
+            .saturating_sub(start)
+            .min(MAX_INSTRUCTION_LENGTH);
+        let mut bytes = [0; MAX_INSTRUCTION_LENGTH];
+        self.memory.read(start, &mut bytes[..len]);
+        let (instruction, length) = decode(&bytes[..len], address)?;
+        if !self.config.profile.supports(&instruction) {
+            return Err(DecodeError::InvalidOpcode {
+                address,
+                opcode: bytes[0],
+            });
         }
+        Ok((instruction, length))
     }
     pub fn register_value(&self, register: &Register) -> Word {
         match register {
@@ -519,7 +395,18 @@ impl Vm {
             Register::ProgramCounter => self.registers.program_counter,
         }
     }
+    /// the stack grows down, so the first push writes to the word before it
+    pub fn stack_pointer(&self) -> Word {
+        self.registers.stack_pointer
+    }
+    pub fn set_stack_pointer(&mut self, value: Word) {
+        self.registers.stack_pointer = value;
+    }
     pub fn set_register_value(&mut self, register: &Register, value: Word) {
+        if self.observer.is_enabled() {
+            let old = self.register_value(register);
+            self.observer.on_register_write(register, old, value);
+        }
         match register {
             Register::GeneralPurpose0 => self.registers.general_purpose_0 = value,
             Register::GeneralPurpose1 => self.registers.general_purpose_1 = value,
@@ -527,122 +414,232 @@ impl Vm {
             Register::ProgramCounter => self.registers.program_counter = value,
         }
     }
-    pub fn set_memory_value(&mut self, address: &Word, value: Word) -> Result<(), String> {
-        let address: usize = (*address)
-            .try_into()
-            .map_err(invalid_architecture_message)?;
-
-        value.to_be_bytes().into_iter().enumerate().try_for_each(
-            |(offset, value)| -> Result<(), String> {
-                let len = self.memory.len();
-                let reference = self.memory.get_mut(address + offset).ok_or_else(|| {
-                    format!(
-                        "cannot get current byte: index {} > {len}",
-                        address + offset,
-                    )
-                })?;
-                *reference = value;
-                Ok(())
-            },
-        )
+    /// writes regardless of regions, which only restrict the program
+    pub fn set_memory_value(&mut self, address: &Word, value: Word) -> Result<(), VmError> {
+        if let Some((device, offset)) = self.device_at_mut(*address) {
+            device.write(offset, value);
+            return Ok(());
+        }
+        if let Some(offset) = self.interrupts.offset(*address) {
+            self.write_interrupt_controller(offset, value);
+            return Ok(());
+        }
+        let address: usize = (*address).try_into().map_err(unsupported_architecture)?;
+
+        if !self
+            .memory
+            .write_word(address, self.config.endianness.word_to_bytes(value))
+        {
+            return Err(VmError::MemoryOutOfBounds {
+                pc: self.instruction_location,
+                address: address as Word,
+                len: self.memory.len(),
+            });
+        }
+        self.instruction_cache.invalidate(address as Word);
+        if let Some(blocks) = &mut self.block_cache {
+            blocks.invalidate(address as Word);
+        }
+        Ok(())
+    }
+    pub(crate) fn memory_len(&self) -> usize {
+        self.memory.len()
     }
-    pub fn memory_value(&self, address: &Word) -> Result<Word, String> {
-        let address: usize = (*address)
-            .try_into()
-            .map_err(invalid_architecture_message)?;
+    pub fn config(&self) -> &VmConfig {
+        &self.config
+    }
+    /// reads from mapped devices don't have side effects
+    pub fn memory_value(&self, address: &Word) -> Result<Word, VmError> {
+        if let Some((device, offset)) = self.device_at(*address) {
+            return Ok(device.peek(offset));
+        }
+        if let Some(offset) = self.interrupts.offset(*address) {
+            return Ok(self.interrupts.peek(offset));
+        }
+        let address: usize = (*address).try_into().map_err(unsupported_architecture)?;
 
         self.memory
-            .get(address..address + 4)
-            .ok_or_else(|| {
-                format!(
-                    "cannot get memory word: index {} > {}",
-                    address + 4,
-                    self.memory.len()
-                )
+            .read_word(address)
+            .ok_or(VmError::MemoryOutOfBounds {
+                pc: self.instruction_location,
+                address: address as Word,
+                len: self.memory.len(),
             })
-            .map(|bytes| u32::from_be_bytes(bytes.try_into().expect("grabbed 4 bytes")))
+            .map(|bytes| self.config.endianness.bytes_to_word(bytes))
+    }
+    /// reads a word on behalf of the executing instruction
+    pub(crate) fn load(&mut self, address: &Word) -> Result<Word, VmError> {
+        self.check_access(
+            self.instruction_location,
+            *address,
+            WORD_SIZE,
+            AccessKind::Read,
+        )?;
+        let value = match self.device_at_mut(*address) {
+            Some((device, offset)) => device.read(offset),
+            None => self.memory_value(address)?,
+        };
+        self.observer.on_memory_read(*address, value);
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(*address, Access::Read, value, value);
+        }
+        Ok(value)
+    }
+    /// writes a word on behalf of the executing instruction
+    pub(crate) fn store(&mut self, address: &Word, value: Word) -> Result<(), VmError> {
+        self.check_access(
+            self.instruction_location,
+            *address,
+            WORD_SIZE,
+            AccessKind::Write,
+        )?;
+        if self.watchpoints.is_empty() && !self.observer.is_enabled() && !self.history.is_enabled()
+        {
+            return self.set_memory_value(address, value);
+        }
+        let old = self.memory_value(address)?;
+        self.set_memory_value(address, value)?;
+        self.record_write(*address, old);
+        self.observer.on_memory_write(*address, old, value);
+        self.check_watchpoints(*address, Access::Write, old, value);
+        Ok(())
     }
     fn run_action_with_config<Action: FnOnce(Word, Word) -> Word>(
         &mut self,
         config: Config,
         action: Action,
-    ) -> Result<(), String> {
+    ) -> Result<(), VmError> {
+        self.try_run_action_with_config(config, |destination, source| {
+            Ok(action(destination, source))
+        })
+    }
+    /// like [`Vm::run_action_with_config`], nothing is written when `action` faults
+    fn try_run_action_with_config<Action: FnOnce(Word, Word) -> Result<Word, VmError>>(
+        &mut self,
+        config: Config,
+        action: Action,
+    ) -> Result<(), VmError> {
         log::debug!("running action with config '{config:?}'");
         match config {
             Config::RegisterFromRegister(destination, source) => {
                 let destination_value = self.register_value(&destination);
                 let source_value = self.register_value(&source);
-                self.set_register_value(&destination, action(destination_value, source_value))
+                self.set_register_value(&destination, action(destination_value, source_value)?)
             }
             Config::RegisterFromImmediate(destination, source) => {
                 let destination_value = self.register_value(&destination);
                 let source_value = source;
-                self.set_register_value(&destination, action(destination_value, source_value))
+                self.set_register_value(&destination, action(destination_value, source_value)?)
             }
             Config::RegisterFromRegisterAddress(destination, source) => {
                 let destination_value = self.register_value(&destination);
-                let source_value = self.memory_value(&self.register_value(&source))?;
-                self.set_register_value(&destination, action(destination_value, source_value))
+                let source_value = self.load(&self.register_value(&source))?;
+                self.set_register_value(&destination, action(destination_value, source_value)?)
             }
             Config::RegisterFromImmediateAddress(destination, source) => {
                 let destination_value = self.register_value(&destination);
-                let source_value = self.memory_value(&source)?;
-                self.set_register_value(&destination, action(destination_value, source_value))
+                let source_value = self.load(&source)?;
+                self.set_register_value(&destination, action(destination_value, source_value)?)
             }
             Config::RegisterAddressFromRegister(destination, source) => {
                 let destination = self.register_value(&destination);
-                let destination_value = self.memory_value(&destination)?;
+                let destination_value = self.load(&destination)?;
                 let source_value = self.register_value(&source);
-                self.set_memory_value(&destination, action(destination_value, source_value))?
+                self.store(&destination, action(destination_value, source_value)?)?
             }
             Config::RegisterAddressFromImmediate(destination, source) => {
                 let destination = self.register_value(&destination);
-                let destination_value = self.memory_value(&destination)?;
+                let destination_value = self.load(&destination)?;
                 let source_value = source;
-                self.set_memory_value(&destination, action(destination_value, source_value))?
+                self.store(&destination, action(destination_value, source_value)?)?
             }
             Config::ImmediateAddressFromRegister(destination, source) => {
-                let destination_value = self.memory_value(&destination)?;
+                let destination_value = self.load(&destination)?;
                 let source_value = self.register_value(&source);
-                self.set_memory_value(&destination, action(destination_value, source_value))?
+                self.store(&destination, action(destination_value, source_value)?)?
             }
             Config::ImmediateAddressFromImmediate(destination, source) => {
-                let destination_value = self.memory_value(&destination)?;
+                let destination_value = self.load(&destination)?;
                 let source_value = source;
-                self.set_memory_value(&destination, action(destination_value, source_value))?
+                self.store(&destination, action(destination_value, source_value)?)?
             }
             Config::ImmediateFromImmediate(destination, source) => {
-                action(destination, source);
+                action(destination, source)?;
             }
             Config::ImmediateFromRegister(destination, source) => {
                 let destination_value = destination;
                 let source_value = self.register_value(&source);
-                action(destination_value, source_value);
+                action(destination_value, source_value)?;
             }
         };
         Ok(())
     }
-    fn run_mov(&mut self, config: Config) -> Result<(), String> {
-        self.run_action_with_config(config, |_destination, source| source)
-    }
-    fn run_not(&mut self, config: NotConfig) -> Result<(), String> {
+    /// the destination is only written, so unlike the other ops it isn't loaded first
+    fn run_mov(&mut self, config: Config) -> Result<(), VmError> {
         match config {
+            Config::RegisterFromRegister(destination, source) => {
+                self.set_register_value(&destination, self.register_value(&source))
+            }
+            Config::RegisterFromImmediate(destination, source) => {
+                self.set_register_value(&destination, source)
+            }
+            Config::RegisterFromRegisterAddress(destination, source) => {
+                let source_value = self.load(&self.register_value(&source))?;
+                self.set_register_value(&destination, source_value)
+            }
+            Config::RegisterFromImmediateAddress(destination, source) => {
+                let source_value = self.load(&source)?;
+                self.set_register_value(&destination, source_value)
+            }
+            Config::RegisterAddressFromRegister(destination, source) => self.store(
+                &self.register_value(&destination),
+                self.register_value(&source),
+            )?,
+            Config::RegisterAddressFromImmediate(destination, source) => {
+                self.store(&self.register_value(&destination), source)?
+            }
+            Config::ImmediateAddressFromRegister(destination, source) => {
+                self.store(&destination, self.register_value(&source))?
+            }
+            Config::ImmediateAddressFromImmediate(destination, source) => {
+                self.store(&destination, source)?
+            }
+            Config::ImmediateFromImmediate(..) | Config::ImmediateFromRegister(..) => {}
+        }
+        Ok(())
+    }
+    fn run_not(&mut self, config: NotConfig) -> Result<(), VmError> {
+        let (result, destination) = match config {
             NotConfig::Register(register) => {
-                self.set_register_value(&register, !self.register_value(&register));
+                let result = alu::not(self.register_value(&register));
+                self.set_register_value(&register, result.value);
+                (result, Some(register))
             }
             NotConfig::RegisterAddress(register) => {
                 let register_value = self.register_value(&register);
-                let value = self.memory_value(&register_value)?;
-                self.set_memory_value(&register_value, !value)?;
+                let result = alu::not(self.load(&register_value)?);
+                self.store(&register_value, result.value)?;
+                (result, None)
             }
             NotConfig::ImmediateAddress(immediate) => {
-                let value = self.memory_value(&immediate)?;
-                self.set_memory_value(&immediate, !value)?;
+                let result = alu::not(self.load(&immediate)?);
+                self.store(&immediate, result.value)?;
+                (result, None)
             }
-        }
+        };
+        self.set_result_flags(destination, result);
         Ok(())
     }
-    fn run_cmp(&mut self, config: Config) -> Result<(), String> {
+    /// sets overflow, carry/borrow and equal from `result`,
+    /// unless the result was written to `fl`, e.g. by `and fl, 0b100`
+    pub(crate) fn set_result_flags(&mut self, destination: Option<Register>, result: AluResult) {
+        if destination == Some(Register::Flag) {
+            return;
+        }
+        let flags = result.flags(self.register_value(&Register::Flag));
+        self.set_register_value(&Register::Flag, flags);
+    }
+    fn run_cmp(&mut self, config: Config) -> Result<(), VmError> {
         let mut new_flag_value = None;
 
         self.run_action_with_config(config, |destination, source| {
@@ -666,64 +663,34 @@ impl Vm {
 
         Ok(())
     }
-    fn run_sub(&mut self, config: Config) -> Result<(), String> {
-        let flags = self.register_value(&Register::Flag);
-        let carry_bit: Word = Flag::CarryOrBorrow.is_active(flags).into();
-
-        let mut set_carry_bit = None;
-
-        self.run_action_with_config(config, |destination, source| {
-            let (result, overflowed) =
-                (destination as i32).overflowing_sub_unsigned(source + carry_bit);
-            set_carry_bit = Some(overflowed);
-            result as u32
-        })?;
-
-        let flag_value = if let Some(set_carry_bit) = set_carry_bit {
-            if set_carry_bit {
-                flags | 0b10
-            } else {
-                flags & !0b10
-            }
-        } else {
-            unreachable!("given closure should always run")
-        };
-
-        self.set_register_value(&Register::Flag, flag_value);
-        Ok(())
-    }
-
-    fn run_add(&mut self, config: Config) -> Result<(), String> {
-        let flags = self.register_value(&Register::Flag);
-        let carry_bit: Word = Flag::CarryOrBorrow.is_active(flags).into();
-
-        let mut set_carry_bit = None;
+    /// runs `add` or `sub`, using the carry/borrow flag as carry/borrow in if configured
+    fn run_with_carry(
+        &mut self,
+        config: Config,
+        op: fn(Word, Word, bool) -> AluResult,
+    ) -> Result<(), VmError> {
+        let carry = self.config.carry_in
+            && Flag::CarryOrBorrow.is_active(self.register_value(&Register::Flag));
 
+        let mut result = None;
         self.run_action_with_config(config, |destination, source| {
-            let (result, overflowed) =
-                (destination as i32).overflowing_add_unsigned(source + carry_bit);
-            set_carry_bit = Some(overflowed);
-            result as u32
+            let value = op(destination, source, carry);
+            result = Some(value);
+            value.value
         })?;
 
-        let flag_value = if let Some(set_carry_bit) = set_carry_bit {
-            if set_carry_bit {
-                flags | 0b10
-            } else {
-                flags & !0b10
-            }
-        } else {
+        let Some(result) = result else {
             unreachable!("given closure should always run")
         };
-
-        self.set_register_value(&Register::Flag, flag_value);
+        self.set_result_flags(config.destination_register(), result);
         Ok(())
     }
     fn run_conditional_jmp(
         &mut self,
         config: ConditionalJmpConfig,
         variant: ConditionalJmpVariant,
-    ) -> Result<(), String> {
+        jmp_variant: JmpVariant,
+    ) -> Result<(), VmError> {
         let should_jump = match variant {
             ConditionalJmpVariant::Jz => |source| source == 0,
             ConditionalJmpVariant::Jnz => |source| source != 0,
@@ -739,12 +706,12 @@ impl Vm {
                 (destination, source)
             }
             ConditionalJmpConfig::RegisterFromRegisterAddress(destination, source) => {
-                let source = self.memory_value(&self.register_value(&source))?;
+                let source = self.load(&self.register_value(&source))?;
                 let destination = self.register_value(&destination);
                 (destination, source)
             }
             ConditionalJmpConfig::RegisterFromImmediateAddress(destination, source) => {
-                let source = self.memory_value(&source)?;
+                let source = self.load(&source)?;
                 let destination = self.register_value(&destination);
                 (destination, source)
             }
@@ -756,49 +723,55 @@ impl Vm {
                 (destination, source)
             }
             ConditionalJmpConfig::ImmediateFromRegisterAddress(destination, source) => {
-                let source = self.memory_value(&self.register_value(&source))?;
+                let source = self.load(&self.register_value(&source))?;
                 (destination, source)
             }
             ConditionalJmpConfig::ImmediateFromImmediateAddress(destination, source) => {
-                let source = self.memory_value(&source)?;
+                let source = self.load(&source)?;
                 (destination, source)
             }
             ConditionalJmpConfig::RegisterAddressFromRegister(destination, source) => {
                 let source = self.register_value(&source);
-                let destination = self.memory_value(&self.register_value(&destination))?;
+                let destination = self.load(&self.register_value(&destination))?;
                 (destination, source)
             }
             ConditionalJmpConfig::RegisterAddressFromImmediate(destination, source) => {
-                let destination = self.memory_value(&self.register_value(&destination))?;
+                let destination = self.load(&self.register_value(&destination))?;
                 (destination, source)
             }
             ConditionalJmpConfig::ImmediateAddressFromRegister(destination, source) => {
                 let source = self.register_value(&source);
-                let destination = self.memory_value(&destination)?;
+                let destination = self.load(&destination)?;
                 (destination, source)
             }
             ConditionalJmpConfig::ImmediateAddressFromImmediate(destination, source) => {
-                let destination = self.memory_value(&destination)?;
+                let destination = self.load(&destination)?;
                 (destination, source)
             }
         };
 
-        if should_jump(source) {
+        let taken = should_jump(source);
+        self.observer.on_branch(self.instruction_location, taken);
+        if taken {
+            let destination = jmp_variant.destination(self.instruction_location, destination);
             self.set_register_value(&Register::ProgramCounter, destination)
         }
 
         Ok(())
     }
 
-    fn run_jmp(&mut self, config: JmpConfig) -> Result<(), String> {
-        let destination = match config {
-            JmpConfig::Register(register) => self.register_value(&register),
-            JmpConfig::Immediate(immediate) => immediate,
-            JmpConfig::RegisterAddress(register) => {
-                self.memory_value(&self.register_value(&register))?
-            }
-            JmpConfig::ImmediateAddress(immediate) => self.memory_value(&immediate)?,
-        };
+    fn jmp_target(&mut self, config: JmpConfig) -> Result<Word, VmError> {
+        match config {
+            JmpConfig::Register(register) => Ok(self.register_value(&register)),
+            JmpConfig::Immediate(immediate) => Ok(immediate),
+            JmpConfig::RegisterAddress(register) => self.load(&self.register_value(&register)),
+            JmpConfig::ImmediateAddress(immediate) => self.load(&immediate),
+        }
+    }
+
+    fn run_jmp(&mut self, config: JmpConfig, variant: JmpVariant) -> Result<(), VmError> {
+        let destination = self.jmp_target(config)?;
+        let destination = variant.destination(self.instruction_location, destination);
 
         self.set_register_value(&Register::ProgramCounter, destination);
 
@@ -810,49 +783,163 @@ impl Vm {
         Ok(())
     }
 
+    pub(crate) fn push(&mut self, value: Word) -> Result<(), VmError> {
+        let stack_pointer = self.registers.stack_pointer.wrapping_sub(4);
+        self.store(&stack_pointer, value)?;
+        self.registers.stack_pointer = stack_pointer;
+        Ok(())
+    }
+
+    pub(crate) fn pop(&mut self) -> Result<Word, VmError> {
+        let stack_pointer = self.registers.stack_pointer;
+        let value = self.load(&stack_pointer)?;
+        self.registers.stack_pointer = stack_pointer.wrapping_add(4);
+        Ok(value)
+    }
+
+    fn run_push(&mut self, config: PushConfig) -> Result<(), VmError> {
+        let value = match config {
+            PushConfig::Register(register) => self.register_value(&register),
+            PushConfig::Immediate(immediate) => immediate,
+            PushConfig::RegisterAddress(register) => self.load(&self.register_value(&register))?,
+            PushConfig::ImmediateAddress(immediate) => self.load(&immediate)?,
+        };
+        self.push(value)
+    }
+
+    fn run_pop(&mut self, config: PopConfig) -> Result<(), VmError> {
+        let stack_pointer = self.registers.stack_pointer;
+        let value = self.load(&stack_pointer)?;
+        match config {
+            PopConfig::Register(register) => self.set_register_value(&register, value),
+            PopConfig::RegisterAddress(register) => {
+                self.store(&self.register_value(&register), value)?
+            }
+            PopConfig::ImmediateAddress(immediate) => self.store(&immediate, value)?,
+        }
+        self.registers.stack_pointer = stack_pointer.wrapping_add(4);
+        Ok(())
+    }
+
+    /// pushes the location after the instruction, which `ret` returns to
+    fn run_call(&mut self, config: CallConfig) -> Result<(), VmError> {
+        let destination = match config {
+            CallConfig::Register(register) => self.register_value(&register),
+            CallConfig::Immediate(immediate) => immediate,
+            CallConfig::RegisterAddress(register) => self.load(&self.register_value(&register))?,
+            CallConfig::ImmediateAddress(immediate) => self.load(&immediate)?,
+        };
+        self.push(self.register_value(&Register::ProgramCounter))?;
+        self.set_register_value(&Register::ProgramCounter, destination);
+        Ok(())
+    }
+
     fn run_generic_math_op(
         &mut self,
         config: Config,
         variant: MathOpVariant,
-    ) -> Result<(), String> {
-        let action: fn(u32, u32) -> u32 = match variant {
-            MathOpVariant::Or => std::ops::BitOr::bitor,
-            MathOpVariant::And => std::ops::BitAnd::bitand,
-            MathOpVariant::Xor => std::ops::BitXor::bitxor,
-            MathOpVariant::Shl => u32::rotate_left,
-            MathOpVariant::Shr => u32::rotate_right,
-            MathOpVariant::Mul => std::ops::Mul::mul,
-            MathOpVariant::IMul => |value, rhs| ((value as i32) * (rhs as i32)) as u32,
-            MathOpVariant::Div => std::ops::Div::div,
-            MathOpVariant::IDiv => |value, rhs| ((value as i32) / (rhs as i32)) as u32,
-            MathOpVariant::Rem => std::ops::Rem::rem,
-        };
+    ) -> Result<(), VmError> {
+        let pc = self.instruction_location;
+        let shift = self.config.shift;
+        let mut result = None;
+        self.try_run_action_with_config(config, |destination, source| {
+            let value = alu::math_op(&variant, destination, source, shift)
+                .ok_or(VmError::DivideByZero { pc })?;
+            result = Some(value);
+            Ok(value.value)
+        })?;
 
-        self.run_action_with_config(config, action)?;
+        let Some(result) = result else {
+            unreachable!("given closure should always run")
+        };
+        self.set_result_flags(config.destination_register(), result);
 
         Ok(())
     }
 
-    pub fn run_next_instruction(&mut self) -> Result<(), String> {
-        if self.register_value(&Register::ProgramCounter) as usize >= self.memory.len() {
-            return Err(String::from("out of instructions"));
+    pub fn is_halted(&self) -> bool {
+        self.hlt_location == Some(self.registers.program_counter)
+    }
+    /// the amount of instructions executed, including ones which faulted
+    pub fn instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
+    /// the [cycles](Instruction::cycles) of the instructions executed, including ones which faulted
+    pub fn cycle_count(&self) -> u64 {
+        self.cycle_count
+    }
+
+    pub fn run_next_instruction(&mut self) -> Result<StepOutcome, VmError> {
+        if self.replay.is_some() {
+            self.replay_inputs()?;
         }
+        self.deliver_interrupt();
         let instruction_location = self.register_value(&Register::ProgramCounter);
+        if let Some(outcome) = self.prepare_step(instruction_location)? {
+            return Ok(outcome);
+        }
+        log::debug!("parsing {instruction_location:#04X}",);
+        let (instruction, length) = self.cached_decode_at(instruction_location)?;
+        self.execute(instruction_location, instruction, length)
+    }
+
+    /// returns the outcome of the step if the instruction at `instruction_location` should not run
+    pub(crate) fn prepare_step(
+        &mut self,
+        instruction_location: Word,
+    ) -> Result<Option<StepOutcome>, VmError> {
+        if instruction_location as usize >= self.memory.len() {
+            return Err(VmError::OutOfInstructions {
+                pc: instruction_location,
+                len: self.memory.len(),
+            });
+        }
 
         if let Some(hlt_location) = self.hlt_location {
             if instruction_location == hlt_location {
-                return Ok(());
+                return Ok(Some(StepOutcome::WaitingForInterrupt));
             }
             self.hlt_location = None;
         }
 
-        log::debug!("parsing {instruction_location:#04X}",);
-        let instruction = self.parse_next_instruction()?;
-        log::debug!("running instruction {instruction:?} at {instruction_location:#04X}",);
+        if self.hit_breakpoint(instruction_location) {
+            return Ok(Some(StepOutcome::Breakpoint(instruction_location)));
+        }
+
+        // the rest of the instruction is checked once it's decoded
+        self.check_access(
+            instruction_location,
+            instruction_location,
+            1,
+            AccessKind::Execute,
+        )?;
+
+        Ok(None)
+    }
+
+    pub(crate) fn execute(
+        &mut self,
+        instruction_location: Word,
+        instruction: Instruction,
+        length: usize,
+    ) -> Result<StepOutcome, VmError> {
+        self.check_access(
+            instruction_location,
+            instruction_location,
+            length as Word,
+            AccessKind::Execute,
+        )?;
+        let next_instruction_location = self.start_instruction(
+            instruction_location,
+            &instruction,
+            length,
+            instruction.cycles(),
+        );
         match instruction {
             Instruction::Nop => (),
             Instruction::Hlt => {
-                self.hlt_location = Some(self.register_value(&Register::ProgramCounter));
+                self.hlt_location = Some(next_instruction_location);
+                return Ok(StepOutcome::Halted);
             }
             Instruction::Mov(config) => self.run_mov(config)?,
             Instruction::Not(config) => self.run_not(config)?,
@@ -861,22 +948,350 @@ impl Vm {
             Instruction::Xor(config) => self.run_generic_math_op(config, MathOpVariant::Xor)?,
             Instruction::Shl(config) => self.run_generic_math_op(config, MathOpVariant::Shl)?,
             Instruction::Shr(config) => self.run_generic_math_op(config, MathOpVariant::Shr)?,
-            Instruction::Add(config) => self.run_add(config)?,
-            Instruction::Sub(config) => self.run_sub(config)?,
+            Instruction::Add(config) => self.run_with_carry(config, alu::add)?,
+            Instruction::Sub(config) => self.run_with_carry(config, alu::sub)?,
             Instruction::Mul(config) => self.run_generic_math_op(config, MathOpVariant::Mul)?,
             Instruction::IMul(config) => self.run_generic_math_op(config, MathOpVariant::IMul)?,
             Instruction::Div(config) => self.run_generic_math_op(config, MathOpVariant::Div)?,
             Instruction::IDiv(config) => self.run_generic_math_op(config, MathOpVariant::IDiv)?,
             Instruction::Rem(config) => self.run_generic_math_op(config, MathOpVariant::Rem)?,
             Instruction::Cmp(config) => self.run_cmp(config)?,
-            Instruction::Jmp(config) => self.run_jmp(config)?,
-            Instruction::Jz(config) => {
-                self.run_conditional_jmp(config, ConditionalJmpVariant::Jz)?
+            Instruction::Jmp(config, variant) => self.run_jmp(config, variant)?,
+            Instruction::Jz(config, variant) => {
+                self.run_conditional_jmp(config, ConditionalJmpVariant::Jz, variant)?
+            }
+            Instruction::Jnz(config, variant) => {
+                self.run_conditional_jmp(config, ConditionalJmpVariant::Jnz, variant)?
             }
-            Instruction::Jnz(config) => {
-                self.run_conditional_jmp(config, ConditionalJmpVariant::Jnz)?
+            Instruction::Push(config) => self.run_push(config)?,
+            Instruction::Pop(config) => self.run_pop(config)?,
+            Instruction::Call(config) => self.run_call(config)?,
+            Instruction::Ret => {
+                let destination = self.pop()?;
+                self.set_register_value(&Register::ProgramCounter, destination)
             }
         }
-        Ok(())
+
+        Ok(self.finish_instruction(instruction_location, next_instruction_location))
+    }
+
+    /// counts the instruction and moves the pc past it before it runs,
+    /// returning the location after it
+    pub(crate) fn start_instruction(
+        &mut self,
+        instruction_location: Word,
+        instruction: &Instruction,
+        length: usize,
+        cycles: u64,
+    ) -> Word {
+        self.instruction_location = instruction_location;
+        self.watchpoint_hit = None;
+        self.record_instruction();
+        self.instruction_count += 1;
+        self.cycle_count += cycles;
+        if !self.devices.is_empty() {
+            self.tick_devices();
+        }
+        let next_instruction_location = instruction_location.wrapping_add(length as Word);
+        self.registers.program_counter = next_instruction_location;
+        log::debug!("running instruction {instruction:?} at {instruction_location:#04X}",);
+        self.observer
+            .on_instruction(instruction_location, instruction);
+        next_instruction_location
+    }
+
+    /// the outcome of an instruction which ran without faulting or halting
+    pub(crate) fn finish_instruction(
+        &mut self,
+        instruction_location: Word,
+        next_instruction_location: Word,
+    ) -> StepOutcome {
+        if let Some(hit) = self.watchpoint_hit.take() {
+            return StepOutcome::Watchpoint(hit);
+        }
+
+        let program_counter = self.register_value(&Register::ProgramCounter);
+        if program_counter != next_instruction_location {
+            return StepOutcome::Jumped {
+                from: instruction_location,
+                to: program_counter,
+            };
+        }
+        StepOutcome::Executed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Config, Flag, Instruction, Register, StepOutcome, Vm};
+    use crate::{
+        arch::Word,
+        block::ExecutionEngine,
+        config::{Endianness, Profile, VmConfig, DEFAULT_MEMORY_SIZE},
+        error::VmError,
+        memory::MemoryBackend,
+        run::StopReason,
+        test_util::assemble,
+    };
+
+    const DESTINATION_ADDRESS: Word = 0x20;
+    const SOURCE_ADDRESS: Word = 0x24;
+
+    type Op = fn(Config) -> Instruction;
+
+    /// every config with `a` as the destination and `b` as the source operand
+    fn configs(a: Word, b: Word) -> [Config; 10] {
+        use Register::{GeneralPurpose0 as R0, GeneralPurpose1 as R1};
+        [
+            Config::RegisterFromRegister(R0, R1),
+            Config::RegisterFromImmediate(R0, b),
+            Config::RegisterFromRegisterAddress(R0, R1),
+            Config::RegisterFromImmediateAddress(R0, SOURCE_ADDRESS),
+            Config::RegisterAddressFromRegister(R0, R1),
+            Config::RegisterAddressFromImmediate(R0, b),
+            Config::ImmediateAddressFromRegister(DESTINATION_ADDRESS, R1),
+            Config::ImmediateAddressFromImmediate(DESTINATION_ADDRESS, b),
+            Config::ImmediateFromImmediate(a, b),
+            Config::ImmediateFromRegister(a, R1),
+        ]
+    }
+
+    /// runs `instruction` with `a` and `b` as operands in `config`,
+    /// returning the stored result, `None` for immediate destinations, and the flags
+    fn run(
+        instruction: Op,
+        config: Config,
+        a: Word,
+        b: Word,
+        flags: Word,
+    ) -> Result<(Option<Word>, Word), VmError> {
+        let mut vm = Vm::new(Vec::new(), VmConfig::new().memory_size(0x40));
+        vm.set_memory_value(&DESTINATION_ADDRESS, a).unwrap();
+        vm.set_memory_value(&SOURCE_ADDRESS, b).unwrap();
+        let (destination_address, source_address) = match config {
+            Config::RegisterAddressFromRegister(..) | Config::RegisterAddressFromImmediate(..) => {
+                (true, false)
+            }
+            Config::RegisterFromRegisterAddress(..) => (false, true),
+            _ => (false, false),
+        };
+        let r0 = if destination_address {
+            DESTINATION_ADDRESS
+        } else {
+            a
+        };
+        let r1 = if source_address { SOURCE_ADDRESS } else { b };
+        vm.set_register_value(&Register::GeneralPurpose0, r0);
+        vm.set_register_value(&Register::GeneralPurpose1, r1);
+        vm.set_register_value(&Register::Flag, flags);
+
+        vm.execute(0, instruction(config), 2)?;
+        let result = match config {
+            Config::RegisterFromRegister(..)
+            | Config::RegisterFromImmediate(..)
+            | Config::RegisterFromRegisterAddress(..)
+            | Config::RegisterFromImmediateAddress(..) => {
+                Some(vm.register_value(&Register::GeneralPurpose0))
+            }
+            Config::RegisterAddressFromRegister(..)
+            | Config::RegisterAddressFromImmediate(..)
+            | Config::ImmediateAddressFromRegister(..)
+            | Config::ImmediateAddressFromImmediate(..) => {
+                Some(vm.memory_value(&DESTINATION_ADDRESS).unwrap())
+            }
+            Config::ImmediateFromImmediate(..) | Config::ImmediateFromRegister(..) => None,
+        };
+        Ok((result, vm.register_value(&Register::Flag)))
+    }
+
+    #[test]
+    fn steps_report_outcomes_until_halted() {
+        let program = assemble("mov r0, 1\njmp skip\nnop\nskip:\nhlt");
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x20));
+        assert_eq!(vm.run_next_instruction(), Ok(StepOutcome::Executed));
+        assert!(!vm.is_halted());
+        let jumped = StepOutcome::Jumped { from: 6, to: 0x0D };
+        assert_eq!(vm.run_next_instruction(), Ok(jumped));
+        assert_eq!(vm.run_next_instruction(), Ok(StepOutcome::Halted));
+        assert!(vm.is_halted());
+
+        // stepping a halted vm doesn't run anything
+        for _ in 0..2 {
+            assert_eq!(
+                vm.run_next_instruction(),
+                Ok(StepOutcome::WaitingForInterrupt)
+            );
+        }
+        assert!(vm.is_halted());
+        assert_eq!(vm.instruction_count(), 3);
+        assert_eq!(vm.register_value(&Register::ProgramCounter), 0x0E);
+
+        // moving the pc away resumes execution
+        vm.set_register_value(&Register::ProgramCounter, 0);
+        assert!(!vm.is_halted());
+        assert_eq!(vm.run_next_instruction(), Ok(StepOutcome::Executed));
+    }
+
+    #[test]
+    fn division_by_zero_faults() {
+        let instructions: [Op; 3] = [Instruction::Div, Instruction::IDiv, Instruction::Rem];
+        for instruction in instructions {
+            for config in configs(7, 0) {
+                assert_eq!(
+                    run(instruction, config, 7, 0, 0),
+                    Err(VmError::DivideByZero { pc: 0 }),
+                    "{:?}",
+                    instruction(config)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn signed_ops_wrap_and_set_overflow() {
+        let cases: [(Op, Word, Word, Word, bool); 5] = [
+            (Instruction::IMul, 6, -7i32 as Word, -42i32 as Word, false),
+            (Instruction::IMul, 0x10000, 0x10000, 0, true),
+            (
+                Instruction::IMul,
+                i32::MIN as Word,
+                -1i32 as Word,
+                i32::MIN as Word,
+                true,
+            ),
+            (Instruction::IDiv, -42i32 as Word, 6, -7i32 as Word, false),
+            (
+                Instruction::IDiv,
+                i32::MIN as Word,
+                -1i32 as Word,
+                i32::MIN as Word,
+                true,
+            ),
+        ];
+        for (instruction, a, b, expected, overflowed) in cases {
+            for config in configs(a, b) {
+                // the overflow flag is cleared by ops which don't overflow
+                let (result, flags) = run(instruction, config, a, b, 0b1).unwrap();
+                let name = format!("{:?}", instruction(config));
+                assert!(result.is_none_or(|result| result == expected), "{name}");
+                assert_eq!(Flag::Overflow.is_active(flags), overflowed, "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn results_set_flags_in_every_config() {
+        let less = Flag::Less.bit();
+        for config in configs(5, 4) {
+            // the carry/borrow flag is used as borrow in
+            let (result, flags) = run(Instruction::Sub, config, 5, 4, less | 0b10).unwrap();
+            assert!(result.is_none_or(|result| result == 0), "{config:?}");
+            assert_eq!(flags, less | Flag::Equal.bit(), "{config:?}");
+        }
+    }
+
+    #[test]
+    fn results_written_to_flags_are_kept() {
+        let program = assemble("cmp r0, r0\nand fl, 0b100\nnot fl\nhlt");
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x40));
+        assert_eq!(vm.run(2).0, StopReason::BudgetExhausted);
+        assert_eq!(vm.register_value(&Register::Flag), 0b100);
+        vm.run(1);
+        assert_eq!(vm.register_value(&Register::Flag), !0b100);
+    }
+
+    #[test]
+    fn config_selects_carry_in_and_endianness() {
+        let program = assemble("add r0, 1\nmov [0x20], r0\nhlt");
+        let config = VmConfig::new().memory_size(0x40);
+        let mut legacy = Vm::new(program.clone(), config);
+        let little_endian = config.carry_in(false).endianness(Endianness::Little);
+        let mut vm = Vm::new(program, little_endian);
+        for vm in [&mut legacy, &mut vm] {
+            vm.set_register_value(&Register::Flag, Flag::CarryOrBorrow.bit());
+            assert_eq!(vm.run(10).0, StopReason::Halted);
+        }
+        assert_eq!(legacy.register_value(&Register::GeneralPurpose0), 2);
+        assert_eq!(legacy.memory_value(&0x1D), Ok(0));
+        assert_eq!(vm.register_value(&Register::GeneralPurpose0), 1);
+        assert_eq!(vm.memory_value(&0x20), Ok(1));
+        assert_eq!(vm.memory_value(&0x1D), Ok(0x0100_0000));
+    }
+
+    #[test]
+    fn default_config_has_memory() {
+        let mut vm = Vm::new(vec![0x01], VmConfig::new());
+        assert_eq!(vm.run(10).0, StopReason::Halted);
+        let last_word = DEFAULT_MEMORY_SIZE as Word - 4;
+        assert_eq!(vm.memory_value(&last_word), Ok(0));
+        assert!(vm.memory_value(&(last_word + 1)).is_err());
+    }
+
+    #[test]
+    fn relative_jumps_run_anywhere_in_memory() {
+        let routine = assemble(
+            "main:\n\
+             mov r0, 3\n\
+             .loop:\n\
+             add r1, 2\n\
+             sub r0, 1\n\
+             jnz rel .loop, r0\n\
+             jmp rel .done\n\
+             mov r1, 0\n\
+             .done:\n\
+             hlt",
+        );
+        let mut program = assemble("jmp 0x30");
+        program.resize(0x30, 0);
+        program.extend(routine);
+        for engine in [ExecutionEngine::Interpreter, ExecutionEngine::BasicBlock] {
+            let mut vm = Vm::new(program.clone(), VmConfig::new().memory_size(0x80));
+            vm.set_execution_engine(engine);
+            assert_eq!(vm.run(100).0, StopReason::Halted);
+            assert_eq!(vm.register_value(&Register::GeneralPurpose1), 6);
+        }
+    }
+
+    #[test]
+    fn stack_instructions_need_the_stack_profile() {
+        let program = assemble(
+            "main:\n\
+             mov r0, 5\n\
+             call double\n\
+             push r0\n\
+             pop [0x60]\n\
+             hlt\n\
+             double:\n\
+             add r0, r0\n\
+             ret",
+        );
+        let config = VmConfig::new().memory_size(0x80);
+        for engine in [ExecutionEngine::Interpreter, ExecutionEngine::BasicBlock] {
+            let mut vm = Vm::new(program.clone(), config.profile(Profile::Stack));
+            vm.set_execution_engine(engine);
+            assert_eq!(vm.run(100).0, StopReason::Halted);
+            assert_eq!(vm.register_value(&Register::GeneralPurpose0), 10);
+            assert_eq!(vm.memory_value(&0x60), Ok(10));
+            assert_eq!(vm.stack_pointer(), 0x80);
+        }
+        let mut vm = Vm::new(program, config);
+        let fault = VmError::InvalidOpcode {
+            pc: 6,
+            opcode: 0x19,
+        };
+        assert_eq!(vm.run(100).0, StopReason::Fault(fault));
+    }
+
+    #[test]
+    fn the_stack_starts_at_the_last_word_of_a_4_gib_address_space() {
+        let config = VmConfig::new()
+            .memory_size(0x1_0000_0000)
+            .memory_backend(MemoryBackend::Paged)
+            .profile(Profile::Stack);
+        let mut vm = Vm::new(assemble("push 7\nhlt"), config);
+        assert_eq!(vm.stack_pointer(), 0xFFFF_FFFC);
+        assert_eq!(vm.run(10).0, StopReason::Halted);
+        assert_eq!(vm.stack_pointer(), 0xFFFF_FFF8);
+        assert_eq!(vm.memory_value(&0xFFFF_FFF8), Ok(7));
     }
 }
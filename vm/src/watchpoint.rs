@@ -0,0 +1,138 @@
+use std::ops::Range;
+
+use crate::{arch::Word, observer::VmObserver, vm::Vm};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// writes which change the stored value
+    Change,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range: Range<Word>,
+    pub kind: WatchKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchpointHit {
+    /// location of the instruction accessing the watched memory
+    pub pc: Word,
+    pub address: Word,
+    pub kind: WatchKind,
+    pub old: Word,
+    pub new: Word,
+}
+
+pub(crate) enum Access {
+    Read,
+    Write,
+}
+
+impl Watchpoint {
+    fn overlaps(&self, address: Word) -> bool {
+        let address = u64::from(address);
+        address < u64::from(self.range.end) && u64::from(self.range.start) < address + 4
+    }
+    fn matches(&self, access: &Access, old: Word, new: Word) -> bool {
+        match (self.kind, access) {
+            (WatchKind::Read, Access::Read) | (WatchKind::Write, Access::Write) => true,
+            (WatchKind::Change, Access::Write) => old != new,
+            _ => false,
+        }
+    }
+}
+
+impl<O: VmObserver> Vm<O> {
+    /// returns an id used to remove the watchpoint
+    pub fn add_watchpoint(&mut self, range: Range<Word>, kind: WatchKind) -> usize {
+        let id = self.next_watchpoint_id;
+        self.next_watchpoint_id += 1;
+        self.watchpoints.insert(id, Watchpoint { range, kind });
+        id
+    }
+    /// returns whether a watchpoint with `id` existed
+    pub fn remove_watchpoint(&mut self, id: usize) -> bool {
+        self.watchpoints.remove(&id).is_some()
+    }
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+    /// watchpoints sorted by id
+    pub fn watchpoints(&self) -> impl Iterator<Item = (usize, &Watchpoint)> {
+        self.watchpoints
+            .iter()
+            .map(|(id, watchpoint)| (*id, watchpoint))
+    }
+    pub(crate) fn check_watchpoints(
+        &mut self,
+        address: Word,
+        access: Access,
+        old: Word,
+        new: Word,
+    ) {
+        if self.watchpoint_hit.is_some() {
+            return;
+        }
+        let hit = self.watchpoints.values().find(|watchpoint| {
+            watchpoint.overlaps(address) && watchpoint.matches(&access, old, new)
+        });
+        self.watchpoint_hit = hit.map(|watchpoint| WatchpointHit {
+            pc: self.instruction_location,
+            address,
+            kind: watchpoint.kind,
+            old,
+            new,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{StopReason, Vm, VmConfig, WatchKind, WatchpointHit};
+
+    #[test]
+    fn change_watchpoint_ignores_identical_writes() {
+        // mov [0x20], 0; mov [0x20], 5; hlt
+        let program = vec![
+            0x02, 0xD0, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, //
+            0x02, 0xD0, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x05, //
+            0x01,
+        ];
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x40));
+        vm.add_watchpoint(0x20..0x24, WatchKind::Change);
+
+        let hit = WatchpointHit {
+            pc: 10,
+            address: 0x20,
+            kind: WatchKind::Change,
+            old: 0,
+            new: 5,
+        };
+        assert_eq!(vm.run(10), (StopReason::Watchpoint(hit), 2));
+        assert_eq!(vm.run(10), (StopReason::Halted, 1));
+    }
+
+    #[test]
+    fn stores_dont_hit_read_watchpoints() {
+        // mov [0x20], 5; mov r0, [0x20]; hlt
+        let program = vec![
+            0x02, 0xD0, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x05, //
+            0x02, 0x30, 0x00, 0x00, 0x00, 0x20, //
+            0x01,
+        ];
+        let mut vm = Vm::new(program, VmConfig::new().memory_size(0x40));
+        vm.add_watchpoint(0x20..0x24, WatchKind::Read);
+
+        let hit = WatchpointHit {
+            pc: 10,
+            address: 0x20,
+            kind: WatchKind::Read,
+            old: 5,
+            new: 5,
+        };
+        assert_eq!(vm.run(10), (StopReason::Watchpoint(hit), 2));
+    }
+}